@@ -1,31 +1,12 @@
 use std::error::Error;
 
 use cloud_datastore_rs::{
-    google::datastore::v1::{
-        key::{path_element::IdType, PathElement},
-        Entity, Key,
-    },
-    Datastore, Kind, TryFromEntity, TryFromEntityError,
+    google::datastore::v1::Entity, Datastore, Kind, TryFromEntity, TryFromEntityError, TypedKey,
 };
 
-#[derive(Debug)]
-struct BookKey(String);
-
-impl From<BookKey> for Key {
-    fn from(book_key: BookKey) -> Self {
-        Key {
-            path: vec![PathElement {
-                kind: "Book".to_string(),
-                id_type: Some(IdType::Name(book_key.0)),
-            }],
-            ..Default::default()
-        }
-    }
-}
-
 #[derive(Debug)]
 struct Book {
-    id: BookKey,
+    id: TypedKey<Book>,
     title: String,
     tags: Vec<String>,
 }
@@ -33,7 +14,7 @@ struct Book {
 impl TryFromEntity for Book {
     fn try_from_entity(value: Entity) -> Result<Self, TryFromEntityError> {
         println!("{:?}", value);
-        let id = BookKey(value.req_key("Book")?.name()?.to_string()); // Ensure the key is of kind 'Book'
+        let id = value.req_typed_key()?; // Ensure the key is of kind 'Book'
         let title = value.req_string("title")?;
         let tags = value.req_string_array("tags")?;
         Ok(Book { id, title, tags })
@@ -49,7 +30,7 @@ impl Kind for Book {
 impl From<Book> for Entity {
     fn from(book: Book) -> Self {
         Entity::builder()
-            .with_key_name("Book", &book.id.0)
+            .with_key(book.id.into())
             .add_string("title", &book.title, true)
             .add_string_array("tags", book.tags)
             .build()
@@ -66,7 +47,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let mut datastore = Datastore::new(project_id, database_id, token_provider).await?;
 
     let book = Book {
-        id: BookKey("book_one".to_string()),
+        id: TypedKey::name("book_one"),
         title: "Book One Title".to_string(),
         tags: vec!["tag_one".to_string(), "tag_two".to_string()],
     };
@@ -74,20 +55,18 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let result = datastore.upsert_entity(book).await?;
     println!("{:?}", result);
 
-    let book: Option<Book> = datastore
-        .lookup_entity(BookKey("book_one".to_string()))
-        .await?;
+    let book: Option<Book> = datastore.lookup_entity(TypedKey::name("book_one")).await?;
     println!("{:?}", book);
 
     let result = datastore
         .upsert_entities(vec![
             Book {
-                id: BookKey("book_three".to_string()),
+                id: TypedKey::name("book_three"),
                 title: "Book Three Title".to_string(),
                 tags: vec!["tag_three".to_string()],
             },
             Book {
-                id: BookKey("book_four".to_string()),
+                id: TypedKey::name("book_four"),
                 title: "Book Four Title".to_string(),
                 tags: vec!["tag_four".to_string()],
             },
@@ -97,7 +76,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     println!("{:?}", result);
 
     datastore
-        .delete_entity(BookKey("book_one".to_string()))
+        .delete_entity(TypedKey::<Book>::name("book_one"))
         .await?;
 
     let all_books = datastore.load_entities::<Book>().await?;
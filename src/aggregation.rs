@@ -0,0 +1,152 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::google::datastore::v1::{
+    aggregation_query::{
+        aggregation::{Avg, Count, Operator, Sum},
+        Aggregation, QueryType,
+    },
+    value::ValueType,
+    AggregationQuery, AggregationResult, PropertyReference, Query, Value,
+};
+
+impl AggregationQuery {
+    /// Create a builder for aggregations over the results of `query`.
+    pub fn builder(query: impl Into<Query>) -> AggregationQueryBuilder {
+        AggregationQueryBuilder {
+            query: AggregationQuery {
+                aggregations: vec![],
+                query_type: Some(QueryType::NestedQuery(query.into())),
+            },
+        }
+    }
+}
+
+/// Builder for creating an [`AggregationQuery`]. Each aggregation is identified by its alias in
+/// the [`AggregationResult`].
+#[derive(Clone, Debug)]
+pub struct AggregationQueryBuilder {
+    query: AggregationQuery,
+}
+
+impl AggregationQueryBuilder {
+    /// Count the number of results.
+    pub fn count<T: Into<String>>(self, alias: T) -> Self {
+        self.add(alias, Operator::Count(Count { up_to: None }))
+    }
+
+    /// Count the number of results, stopping at `up_to`.
+    pub fn count_up_to<T: Into<String>>(self, alias: T, up_to: i64) -> Self {
+        self.add(alias, Operator::Count(Count { up_to: Some(up_to) }))
+    }
+
+    /// Sum the numeric values of a property.
+    pub fn sum<T: Into<String>>(self, alias: T, property: T) -> Self {
+        let property = Some(PropertyReference {
+            name: property.into(),
+        });
+        self.add(alias, Operator::Sum(Sum { property }))
+    }
+
+    /// Average the numeric values of a property.
+    pub fn avg<T: Into<String>>(self, alias: T, property: T) -> Self {
+        let property = Some(PropertyReference {
+            name: property.into(),
+        });
+        self.add(alias, Operator::Avg(Avg { property }))
+    }
+
+    fn add<T: Into<String>>(mut self, alias: T, operator: Operator) -> Self {
+        self.query.aggregations.push(Aggregation {
+            alias: alias.into(),
+            operator: Some(operator),
+        });
+        self
+    }
+
+    /// Builds the aggregation query.
+    pub fn build(self) -> AggregationQuery {
+        self.query
+    }
+}
+
+impl From<AggregationQueryBuilder> for AggregationQuery {
+    fn from(builder: AggregationQueryBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// A numeric aggregation result. Sums are integers when all summed values are integers, and
+/// doubles otherwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Numeric {
+    Integer(i64),
+    Double(f64),
+}
+
+impl Numeric {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Numeric::Integer(i) => *i as f64,
+            Numeric::Double(d) => *d,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AggregationValueError(String);
+
+impl Error for AggregationValueError {}
+
+impl Display for AggregationValueError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AggregationResult {
+    /// The result of a `count` aggregation.
+    pub fn count(&self, alias: &str) -> Result<i64, AggregationValueError> {
+        match self.value_type(alias)? {
+            Some(ValueType::IntegerValue(count)) => Ok(*count),
+            _ => Err(AggregationValueError(format!(
+                "Aggregation {alias} is not an integer"
+            ))),
+        }
+    }
+
+    /// The result of a `sum` aggregation. The sum of no values is `0`.
+    pub fn sum(&self, alias: &str) -> Result<Numeric, AggregationValueError> {
+        match self.value_type(alias)? {
+            Some(ValueType::IntegerValue(sum)) => Ok(Numeric::Integer(*sum)),
+            Some(ValueType::DoubleValue(sum)) => Ok(Numeric::Double(*sum)),
+            Some(ValueType::NullValue(_)) | None => Ok(Numeric::Integer(0)),
+            _ => Err(AggregationValueError(format!(
+                "Aggregation {alias} is not numeric"
+            ))),
+        }
+    }
+
+    /// The result of an `avg` aggregation, or `None` if there were no values to average.
+    pub fn avg(&self, alias: &str) -> Result<Option<f64>, AggregationValueError> {
+        match self.value_type(alias)? {
+            Some(ValueType::DoubleValue(avg)) => Ok(Some(*avg)),
+            Some(ValueType::IntegerValue(avg)) => Ok(Some(*avg as f64)),
+            Some(ValueType::NullValue(_)) | None => Ok(None),
+            _ => Err(AggregationValueError(format!(
+                "Aggregation {alias} is not numeric"
+            ))),
+        }
+    }
+
+    fn value_type(&self, alias: &str) -> Result<Option<&ValueType>, AggregationValueError> {
+        match self.aggregate_properties.get(alias) {
+            Some(Value { value_type, .. }) => Ok(value_type.as_ref()),
+            None => Err(AggregationValueError(format!(
+                "Missing aggregation {alias}"
+            ))),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::google::datastore::v1::{mutation::Operation, Entity, Key, Mutation, PropertyTransform};
+use crate::transform::transform_mutation;
+
+///
+/// A list of mutations, of any kind of entity, to commit together with
+/// [`Datastore::commit_batch`](crate::Datastore::commit_batch).
+///
+/// The results of the commit are returned in the order the mutations were added to the batch.
+///
+/// ```ignore
+/// let mut batch = WriteBatch::new();
+/// batch.insert_entity(book);
+/// batch.update_entity(author);
+/// batch.delete_entity(old_review_key);
+/// batch.transform(stats_key, vec![PropertyTransform::increment("books", 1)]);
+/// let results = datastore.commit_batch(batch, CommitMode::Transactional).await?;
+/// ```
+///
+#[derive(Clone, Debug, Default)]
+pub struct WriteBatch {
+    mutations: Vec<Mutation>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        WriteBatch::default()
+    }
+
+    /// Add an insert of an entity. A transactional commit fails with
+    /// [`CloudDatastoreError::AlreadyExists`](crate::CloudDatastoreError::AlreadyExists) if an
+    /// entity with the same key already exists.
+    pub fn insert_entity(&mut self, entity: impl Into<Entity>) {
+        self.push_operation(Operation::Insert(entity.into()));
+    }
+
+    /// Add an update of an entity. A transactional commit fails with
+    /// [`CloudDatastoreError::NotFound`](crate::CloudDatastoreError::NotFound) if no entity with
+    /// the same key exists.
+    pub fn update_entity(&mut self, entity: impl Into<Entity>) {
+        self.push_operation(Operation::Update(entity.into()));
+    }
+
+    /// Add an upsert of an entity.
+    pub fn upsert_entity(&mut self, entity: impl Into<Entity>) {
+        self.push_operation(Operation::Upsert(entity.into()));
+    }
+
+    /// Add a delete of an entity.
+    pub fn delete_entity(&mut self, key: impl Into<Key>) {
+        self.push_operation(Operation::Delete(key.into()));
+    }
+
+    /// Add property transforms of the entity with `key`, as done by
+    /// [`Datastore::transform`](crate::Datastore::transform). The values of the transformed
+    /// properties are in the `transform_results` of the mutation result.
+    pub fn transform(&mut self, key: impl Into<Key>, transforms: Vec<PropertyTransform>) {
+        self.mutations
+            .push(transform_mutation(key.into(), None, transforms));
+    }
+
+    /// Add a mutation built elsewhere, for example with conflict detection.
+    pub fn push(&mut self, mutation: Mutation) {
+        self.mutations.push(mutation);
+    }
+
+    pub fn len(&self) -> usize {
+        self.mutations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mutations.is_empty()
+    }
+
+    pub(crate) fn into_mutations(self) -> Vec<Mutation> {
+        self.mutations
+    }
+
+    fn push_operation(&mut self, operation: Operation) {
+        self.mutations.push(Mutation {
+            operation: Some(operation),
+            ..Default::default()
+        });
+    }
+}
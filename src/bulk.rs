@@ -0,0 +1,200 @@
+use std::ops::Range;
+
+use prost::Message;
+
+use crate::google::datastore::v1::{Mutation, MutationResult};
+use crate::{CloudDatastoreError, CommitMode};
+
+///
+/// Controls how [`Datastore::bulk_write`] splits mutations into commits.
+///
+/// The defaults are the limits of a single Datastore commit: 500 mutations and 10 MiB.
+///
+#[derive(Clone, Debug)]
+pub struct BulkWriteOptions {
+    /// Maximum number of mutations per commit.
+    pub max_mutations: usize,
+    /// Maximum encoded size of a commit request, in bytes.
+    pub max_request_bytes: usize,
+    /// Maximum number of commits in flight at the same time.
+    pub max_concurrency: usize,
+    /// Commit mode of each chunk. Transactional chunks are applied atomically, but chunks are
+    /// always independent of each other.
+    pub mode: CommitMode,
+}
+
+impl Default for BulkWriteOptions {
+    fn default() -> Self {
+        BulkWriteOptions {
+            max_mutations: 500,
+            max_request_bytes: 10 * 1024 * 1024,
+            max_concurrency: 8,
+            mode: CommitMode::Transactional,
+        }
+    }
+}
+
+/// The outcome of a [`Datastore::bulk_write`], with one entry per commit in input order.
+#[derive(Debug, Default)]
+pub struct BulkWriteReport {
+    pub chunks: Vec<ChunkResult>,
+}
+
+/// The outcome of committing one chunk of a bulk write.
+#[derive(Debug)]
+pub struct ChunkResult {
+    /// The positions of the chunk's mutations in the input.
+    pub range: Range<usize>,
+    /// The results of the chunk's mutations, aligned with `range`, or the error of the commit.
+    pub result: Result<Vec<MutationResult>, CloudDatastoreError>,
+}
+
+impl BulkWriteReport {
+    /// Whether all chunks were committed.
+    pub fn is_success(&self) -> bool {
+        self.chunks.iter().all(|chunk| chunk.result.is_ok())
+    }
+
+    /// The chunks that were committed.
+    pub fn succeeded(&self) -> impl Iterator<Item = &ChunkResult> {
+        self.chunks.iter().filter(|chunk| chunk.result.is_ok())
+    }
+
+    /// The chunks that failed to commit.
+    pub fn failed(&self) -> impl Iterator<Item = &ChunkResult> {
+        self.chunks.iter().filter(|chunk| chunk.result.is_err())
+    }
+}
+
+/// Split `mutations` into chunks of at most `max_count` mutations, with encoded mutations adding
+/// up to at most `max_bytes`. A mutation larger than `max_bytes` gets a chunk of its own.
+pub(crate) fn chunk_mutations(
+    mutations: Vec<Mutation>,
+    max_count: usize,
+    max_bytes: usize,
+) -> Vec<(Range<usize>, Vec<Mutation>)> {
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    let mut chunk_bytes = 0;
+    let mut start = 0;
+
+    for (i, mutation) in mutations.into_iter().enumerate() {
+        let len = mutation.encoded_len();
+        // Field tag, length prefix and the message itself.
+        let bytes = 1 + prost::length_delimiter_len(len) + len;
+        if !chunk.is_empty() && (chunk.len() >= max_count || chunk_bytes + bytes > max_bytes) {
+            chunks.push((start..i, std::mem::take(&mut chunk)));
+            chunk_bytes = 0;
+            start = i;
+        }
+        chunk.push(mutation);
+        chunk_bytes += bytes;
+    }
+
+    if !chunk.is_empty() {
+        chunks.push((start..start + chunk.len(), chunk));
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::google::datastore::v1::{mutation::Operation, CommitRequest, Key};
+
+    use super::*;
+
+    fn delete(name: &str) -> Mutation {
+        Mutation {
+            operation: Some(Operation::Delete(Key::builder().name("Book", name).build())),
+            ..Default::default()
+        }
+    }
+
+    /// The number of bytes `mutations` add to a commit request.
+    fn request_bytes(mutations: &[Mutation]) -> usize {
+        CommitRequest {
+            mutations: mutations.to_vec(),
+            ..Default::default()
+        }
+        .encoded_len()
+    }
+
+    fn ranges(chunks: &[(Range<usize>, Vec<Mutation>)]) -> Vec<Range<usize>> {
+        chunks.iter().map(|(range, _)| range.clone()).collect()
+    }
+
+    fn assert_ranges_match_input(mutations: &[Mutation], chunks: &[(Range<usize>, Vec<Mutation>)]) {
+        let mut next = 0;
+        for (range, chunk) in chunks {
+            assert_eq!(range.start, next);
+            assert_eq!(&mutations[range.clone()], chunk.as_slice());
+            next = range.end;
+        }
+        assert_eq!(next, mutations.len());
+    }
+
+    #[test]
+    fn empty() {
+        assert!(chunk_mutations(vec![], 500, 1024).is_empty());
+    }
+
+    #[test]
+    fn count_limit() {
+        let mutations: Vec<_> = (0..7).map(|i| delete(&i.to_string())).collect();
+        let chunks = chunk_mutations(mutations.clone(), 3, usize::MAX);
+        assert_eq!(ranges(&chunks), [0..3, 3..6, 6..7]);
+        assert_ranges_match_input(&mutations, &chunks);
+
+        let chunks = chunk_mutations(mutations.clone(), 7, usize::MAX);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].0, 0..7);
+    }
+
+    #[test]
+    fn byte_limit() {
+        let mutations: Vec<_> = ["a", "bb", "ccc", "dddd", "eeeee", "f"]
+            .iter()
+            .map(|name| delete(&name.repeat(20)))
+            .collect();
+        let one = request_bytes(&mutations[..1]);
+        let max_bytes = request_bytes(&mutations[..3]);
+
+        let chunks = chunk_mutations(mutations.clone(), 500, max_bytes);
+        assert_eq!(ranges(&chunks), [0..3, 3..4, 4..6]);
+        assert_ranges_match_input(&mutations, &chunks);
+        for (_, chunk) in &chunks {
+            assert!(request_bytes(chunk) <= max_bytes);
+        }
+
+        // The limit is inclusive.
+        let chunks = chunk_mutations(mutations[..3].to_vec(), 500, max_bytes - 1);
+        assert_eq!(ranges(&chunks), [0..2, 2..3]);
+        let chunks = chunk_mutations(mutations[..1].to_vec(), 500, one);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].0, 0..1);
+
+        // Whichever limit is reached first ends the chunk.
+        let chunks = chunk_mutations(mutations.clone(), 2, max_bytes);
+        assert_eq!(ranges(&chunks), [0..2, 2..3, 3..4, 4..6]);
+        assert_ranges_match_input(&mutations, &chunks);
+    }
+
+    #[test]
+    fn oversized_mutation() {
+        let mutations = vec![
+            delete("small"),
+            delete(&"x".repeat(1000)),
+            delete("small"),
+            delete("small"),
+        ];
+        let max_bytes = request_bytes(&mutations[2..]);
+
+        let chunks = chunk_mutations(mutations.clone(), 500, max_bytes);
+        assert_eq!(ranges(&chunks), [0..1, 1..2, 2..4]);
+        assert_ranges_match_input(&mutations, &chunks);
+
+        let chunks = chunk_mutations(mutations[1..2].to_vec(), 500, 10);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].0, 0..1);
+    }
+}
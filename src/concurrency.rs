@@ -0,0 +1,97 @@
+use prost_types::Timestamp;
+
+use crate::{
+    google::datastore::v1::{
+        mutation::{ConflictDetectionStrategy, ConflictResolutionStrategy},
+        CommitResponse, Key, MutationResult, PropertyTransform,
+    },
+    CloudDatastoreError,
+};
+
+///
+/// The version and timestamps of an entity, as returned by lookups and mutations.
+///
+/// Passing the metadata of a previously read entity to a checked mutation, such as
+/// [`Datastore::update_entity_checked`](crate::Datastore::update_entity_checked), makes the
+/// mutation fail with [`CloudDatastoreError::Conflict`](crate::CloudDatastoreError::Conflict) if
+/// the entity changed in between.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntityMetadata {
+    /// The version of the entity. Every mutation of an entity increases its version.
+    pub version: i64,
+    /// The time at which the entity was created.
+    pub create_time: Option<Timestamp>,
+    /// The time at which the entity was last changed.
+    pub update_time: Option<Timestamp>,
+}
+
+/// An entity decoded into `T`, together with its metadata.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Versioned<T> {
+    pub entity: T,
+    pub metadata: EntityMetadata,
+}
+
+///
+/// Options for [`Datastore::upsert_entity_with_options`](crate::Datastore::upsert_entity_with_options).
+///
+/// Without `conflict_detection`, an upsert always overwrites the entity. With it, Datastore
+/// compares the entity on the server with the expected version or update time, and on a conflict
+/// applies `conflict_resolution`:
+///
+/// - [`ConflictResolutionStrategy::ServerValue`], the default, keeps the entity on the server.
+///   The upsert is skipped and its result has `conflict_detected` set, while the rest of the
+///   commit succeeds.
+/// - [`ConflictResolutionStrategy::Fail`] fails the whole commit.
+///
+/// `transforms` are part of the same mutation: they are applied after the entity is written, and
+/// are skipped together with it when a conflict is resolved in favour of the server value. A
+/// retried write that carries the version it was based on therefore neither overwrites
+/// properties computed by the server in the meantime nor applies its increments twice.
+///
+#[derive(Clone, Debug, Default)]
+pub struct UpsertOptions {
+    /// The version or update time the entity is expected to have.
+    pub conflict_detection: Option<ConflictDetectionStrategy>,
+    /// How to resolve a detected conflict. Only used with `conflict_detection`.
+    pub conflict_resolution: ConflictResolutionStrategy,
+    /// Transforms to apply after the upsert.
+    pub transforms: Vec<PropertyTransform>,
+}
+
+impl EntityMetadata {
+    /// Detect conflicts by comparing the update time of the entity instead of its version.
+    pub fn update_time_strategy(&self) -> Option<ConflictDetectionStrategy> {
+        self.update_time.map(ConflictDetectionStrategy::UpdateTime)
+    }
+}
+
+impl From<&MutationResult> for EntityMetadata {
+    fn from(result: &MutationResult) -> Self {
+        EntityMetadata {
+            version: result.version,
+            create_time: result.create_time,
+            update_time: result.update_time,
+        }
+    }
+}
+
+impl From<&EntityMetadata> for ConflictDetectionStrategy {
+    fn from(metadata: &EntityMetadata) -> Self {
+        ConflictDetectionStrategy::BaseVersion(metadata.version)
+    }
+}
+
+/// The [`CloudDatastoreError::Conflict`] for the first mutation of `response` for which Datastore
+/// detected a conflict, if any. `keys` are the keys of the mutations, in the order they were sent.
+pub(crate) fn conflict_error(
+    response: &CommitResponse,
+    keys: &[Key],
+) -> Option<CloudDatastoreError> {
+    response
+        .mutation_results
+        .iter()
+        .position(|result| result.conflict_detected)
+        .map(|i| CloudDatastoreError::Conflict(keys.get(i).cloned().unwrap_or_default()))
+}
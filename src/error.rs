@@ -1,16 +1,24 @@
 use std::error::Error;
 use std::fmt::Display;
 
-use crate::TryFromEntityError;
+use crate::{google::datastore::v1::Key, AggregationValueError, TryFromEntityError};
 use http::uri::InvalidUri;
 use http::Error as HttpError;
 use tonic::transport::Error as TransportError;
-use tonic::Status;
+use tonic::{Code, Status};
 
 #[derive(Debug)]
 pub enum CloudDatastoreError {
     GrcpError(Status),
+    /// An insert failed because an entity with the same key already exists.
+    AlreadyExists(Status),
+    /// An update failed because no entity with the same key exists.
+    NotFound(Status),
+    /// A checked mutation was not applied because the entity with this key changed since it was
+    /// read.
+    Conflict(Key),
     EntityConversionError(TryFromEntityError),
+    AggregationConversionError(AggregationValueError),
     TransportError(TransportError),
     InvalidUri(InvalidUri),
     HttpError(HttpError),
@@ -18,13 +26,40 @@ pub enum CloudDatastoreError {
 
 impl Error for CloudDatastoreError {}
 
+impl CloudDatastoreError {
+    /// Map the statuses returned when an insert or update conflicts with the existence of an
+    /// entity to their dedicated variants.
+    pub(crate) fn classify_mutation_error(self) -> Self {
+        match self {
+            CloudDatastoreError::GrcpError(status) => match status.code() {
+                Code::AlreadyExists => CloudDatastoreError::AlreadyExists(status),
+                Code::NotFound => CloudDatastoreError::NotFound(status),
+                _ => CloudDatastoreError::GrcpError(status),
+            },
+            error => error,
+        }
+    }
+}
+
 impl Display for CloudDatastoreError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             CloudDatastoreError::GrcpError(status) => write!(f, "gRPC error: {}", status),
+            CloudDatastoreError::AlreadyExists(status) => {
+                write!(f, "Entity already exists: {}", status.message())
+            }
+            CloudDatastoreError::NotFound(status) => {
+                write!(f, "Entity not found: {}", status.message())
+            }
+            CloudDatastoreError::Conflict(key) => {
+                write!(f, "Entity changed since it was read: {:?}", key)
+            }
             CloudDatastoreError::EntityConversionError(error) => {
                 write!(f, "Entity conversion error: {}", error)
             }
+            CloudDatastoreError::AggregationConversionError(error) => {
+                write!(f, "Aggregation conversion error: {}", error)
+            }
             CloudDatastoreError::TransportError(error) => {
                 write!(f, "Transport error: {}", error)
             }
@@ -46,6 +81,12 @@ impl From<TryFromEntityError> for CloudDatastoreError {
     }
 }
 
+impl From<AggregationValueError> for CloudDatastoreError {
+    fn from(error: AggregationValueError) -> Self {
+        CloudDatastoreError::AggregationConversionError(error)
+    }
+}
+
 impl From<TransportError> for CloudDatastoreError {
     fn from(error: TransportError) -> Self {
         CloudDatastoreError::TransportError(error)
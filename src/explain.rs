@@ -0,0 +1,151 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+use prost_types::{value::Kind as StructKind, Struct};
+
+use crate::google::datastore::v1::ExplainMetrics;
+
+///
+/// The plan and, when the query was analyzed, the execution statistics of a query, as returned by
+/// [`Datastore::explain`](crate::Datastore::explain).
+///
+/// The `Display` implementation prints a human readable summary of the report.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExplainReport {
+    /// The indexes selected for the query, for example
+    /// `{"query_scope": "Collection", "properties": "(year ASC, __name__ ASC)"}`.
+    pub indexes_used: Vec<BTreeMap<String, StatValue>>,
+    /// Statistics from executing the query. Only present when the query was analyzed.
+    pub execution_stats: Option<ExecutionReport>,
+}
+
+/// Statistics from executing a query.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExecutionReport {
+    /// Total number of results returned, including entities, projections, aggregation results and
+    /// keys.
+    pub results_returned: i64,
+    /// Total time to execute the query in the backend.
+    pub execution_duration: Option<Duration>,
+    /// Total billable read operations.
+    pub read_operations: i64,
+    /// Debugging statistics, such as the number of index entries and entities scanned. The
+    /// contents are subject to change.
+    pub debug_stats: BTreeMap<String, StatValue>,
+}
+
+/// A plain Rust representation of a `google.protobuf.Value` found in explain metrics.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<StatValue>),
+    Map(BTreeMap<String, StatValue>),
+}
+
+impl From<ExplainMetrics> for ExplainReport {
+    fn from(metrics: ExplainMetrics) -> Self {
+        ExplainReport {
+            indexes_used: metrics
+                .plan_summary
+                .map(|plan| plan.indexes_used.into_iter().map(struct_to_map).collect())
+                .unwrap_or_default(),
+            execution_stats: metrics.execution_stats.map(|stats| ExecutionReport {
+                results_returned: stats.results_returned,
+                execution_duration: stats
+                    .execution_duration
+                    .and_then(|d| Duration::try_from(d).ok()),
+                read_operations: stats.read_operations,
+                debug_stats: stats.debug_stats.map(struct_to_map).unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+impl From<prost_types::Value> for StatValue {
+    fn from(value: prost_types::Value) -> Self {
+        match value.kind {
+            None | Some(StructKind::NullValue(_)) => StatValue::Null,
+            Some(StructKind::BoolValue(b)) => StatValue::Bool(b),
+            Some(StructKind::NumberValue(n)) => StatValue::Number(n),
+            Some(StructKind::StringValue(s)) => StatValue::String(s),
+            Some(StructKind::ListValue(list)) => {
+                StatValue::List(list.values.into_iter().map(Into::into).collect())
+            }
+            Some(StructKind::StructValue(s)) => StatValue::Map(struct_to_map(s)),
+        }
+    }
+}
+
+fn struct_to_map(s: Struct) -> BTreeMap<String, StatValue> {
+    s.fields.into_iter().map(|(k, v)| (k, v.into())).collect()
+}
+
+impl Display for StatValue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            StatValue::Null => write!(f, "null"),
+            StatValue::Bool(b) => write!(f, "{b}"),
+            StatValue::Number(n) => write!(f, "{n}"),
+            StatValue::String(s) => write!(f, "{s}"),
+            StatValue::List(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            StatValue::Map(map) => write_map(f, map),
+        }
+    }
+}
+
+fn write_map(f: &mut Formatter, map: &BTreeMap<String, StatValue>) -> fmt::Result {
+    write!(f, "{{")?;
+    for (i, (key, value)) in map.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{key}: {value}")?;
+    }
+    write!(f, "}}")
+}
+
+impl Display for ExplainReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "Indexes used:")?;
+        if self.indexes_used.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for index in &self.indexes_used {
+            write!(f, "  - ")?;
+            write_map(f, index)?;
+            writeln!(f)?;
+        }
+
+        let Some(stats) = &self.execution_stats else {
+            return write!(f, "Execution stats: not analyzed");
+        };
+
+        writeln!(f, "Execution stats:")?;
+        writeln!(f, "  Results returned: {}", stats.results_returned)?;
+        writeln!(f, "  Read operations: {}", stats.read_operations)?;
+        if let Some(duration) = stats.execution_duration {
+            writeln!(f, "  Execution duration: {duration:?}")?;
+        }
+        write!(f, "  Debug stats:")?;
+        for (key, value) in &stats.debug_stats {
+            write!(f, "\n    {key}: {value}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+mod parser;
+mod writer;
+
+use std::marker::PhantomData;
+
+use crate::google::datastore::v1::{
+    gql_query_parameter::ParameterType, run_query_request::QueryType, value::ValueType, GqlQuery,
+    GqlQueryParameter, Value,
+};
+use crate::{decode_entity_results, CloudDatastoreError, Cursor, Datastore, TryFromEntity};
+
+pub use parser::GqlParseError;
+
+///
+/// Builder for running a GQL query, created with [`Datastore::gql`].
+///
+/// Named parameters (`@name`) are bound with [`GqlQueryBuilder::bind`], and positional parameters
+/// (`@1`, `@2`, ...) with [`GqlQueryBuilder::bind_positional`], in order. Literal values in the
+/// query string are rejected by Datastore unless [`GqlQueryBuilder::allow_literals`] is set.
+///
+pub struct GqlQueryBuilder<'a, T> {
+    datastore: &'a mut Datastore,
+    query: GqlQuery,
+    _entity: PhantomData<T>,
+}
+
+impl<'a, T: TryFromEntity> GqlQueryBuilder<'a, T> {
+    pub(crate) fn new(datastore: &'a mut Datastore, query_string: String) -> Self {
+        GqlQueryBuilder {
+            datastore,
+            query: GqlQuery {
+                query_string,
+                ..Default::default()
+            },
+            _entity: PhantomData,
+        }
+    }
+
+    /// Allow literal values in the query string.
+    pub fn allow_literals(mut self, allow_literals: bool) -> Self {
+        self.query.allow_literals = allow_literals;
+        self
+    }
+
+    /// Bind a value to the named parameter `@name`.
+    pub fn bind<N: Into<String>, V: Into<ValueType>>(mut self, name: N, value: V) -> Self {
+        self.query
+            .named_bindings
+            .insert(name.into(), value_parameter(value));
+        self
+    }
+
+    /// Bind a cursor to the named parameter `@name`.
+    pub fn bind_cursor<N: Into<String>>(mut self, name: N, cursor: Cursor) -> Self {
+        self.query
+            .named_bindings
+            .insert(name.into(), cursor_parameter(cursor));
+        self
+    }
+
+    /// Bind a value to the next positional parameter, starting at `@1`.
+    pub fn bind_positional<V: Into<ValueType>>(mut self, value: V) -> Self {
+        self.query.positional_bindings.push(value_parameter(value));
+        self
+    }
+
+    /// Bind a cursor to the next positional parameter, starting at `@1`.
+    pub fn bind_positional_cursor(mut self, cursor: Cursor) -> Self {
+        self.query
+            .positional_bindings
+            .push(cursor_parameter(cursor));
+        self
+    }
+
+    /// Builds the GQL query.
+    pub fn build(self) -> GqlQuery {
+        self.query
+    }
+
+    /// Run the query, fetching all of its results.
+    pub async fn fetch(self) -> Result<Vec<T>, CloudDatastoreError> {
+        let (entity_results, _) = self
+            .datastore
+            .fetch_entity_results(QueryType::GqlQuery(self.query), None)
+            .await?;
+        Ok(decode_entity_results(entity_results)?)
+    }
+
+    /// Run the query, fetching a single page of results up to the `LIMIT` of the query, and the
+    /// cursor after the page if there may be more results. See [`Datastore::query_page`].
+    pub async fn fetch_page(self) -> Result<(Vec<T>, Option<Cursor>), CloudDatastoreError> {
+        let (entity_results, cursor) = self
+            .datastore
+            .fetch_entity_results(QueryType::GqlQuery(self.query), None)
+            .await?;
+        Ok((decode_entity_results(entity_results)?, cursor))
+    }
+}
+
+fn value_parameter<V: Into<ValueType>>(value: V) -> GqlQueryParameter {
+    GqlQueryParameter {
+        parameter_type: Some(ParameterType::Value(Value {
+            value_type: Some(value.into()),
+            ..Default::default()
+        })),
+    }
+}
+
+fn cursor_parameter(cursor: Cursor) -> GqlQueryParameter {
+    GqlQueryParameter {
+        parameter_type: Some(ParameterType::Cursor(cursor.into())),
+    }
+}
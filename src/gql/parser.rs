@@ -0,0 +1,1374 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use base64::{
+    alphabet::URL_SAFE,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use prost_types::Timestamp;
+
+use crate::google::datastore::v1::{
+    gql_query_parameter::ParameterType,
+    key::{path_element::IdType, PathElement},
+    property_filter::Operator,
+    property_order::Direction,
+    value::ValueType,
+    ArrayValue, Filter, GqlQuery, GqlQueryParameter, Key, KindExpression, PartitionId, Projection,
+    PropertyOrder, PropertyReference, Query, Value,
+};
+
+/// Engine for `BLOB` literals, which hold URL-safe base64 with optional padding.
+const BLOB_ENGINE: GeneralPurpose = GeneralPurpose::new(
+    &URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+const KEYWORDS: &[&str] = &[
+    "AND",
+    "ANCESTOR",
+    "ARRAY",
+    "ASC",
+    "BLOB",
+    "BY",
+    "CONTAINS",
+    "DATETIME",
+    "DESC",
+    "DESCENDANT",
+    "DISTINCT",
+    "FALSE",
+    "FROM",
+    "HAS",
+    "IN",
+    "IS",
+    "KEY",
+    "LIMIT",
+    "NAMESPACE",
+    "NOT",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "PROJECT",
+    "SELECT",
+    "TRUE",
+    "WHERE",
+];
+
+impl Query {
+    ///
+    /// Parse a GQL query string into a structured query.
+    ///
+    /// Literal values are allowed. Queries with parameters (`@name` or `@1`) must be parsed with
+    /// [`Query::from_gql_query`], which resolves them from the bindings of a [`GqlQuery`].
+    ///
+    pub fn from_gql(gql: &str) -> Result<Query, GqlParseError> {
+        Parser::new(gql, None)?.parse()
+    }
+
+    ///
+    /// Parse a [`GqlQuery`] into a structured query, resolving its named and positional bindings.
+    ///
+    /// As with Datastore, literal values are rejected unless `allow_literals` is set.
+    ///
+    pub fn from_gql_query(gql: &GqlQuery) -> Result<Query, GqlParseError> {
+        Parser::new(&gql.query_string, Some(gql))?.parse()
+    }
+}
+
+/// A syntax or binding error in a GQL query, with the position at which it was detected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GqlParseError {
+    message: String,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl GqlParseError {
+    fn new(input: &str, offset: usize, message: String) -> Self {
+        let before = &input[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let column = before[line_start..].chars().count() + 1;
+        GqlParseError {
+            message,
+            offset,
+            line,
+            column,
+        }
+    }
+
+    /// Description of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Byte offset in the query string at which the error was detected.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Line at which the error was detected, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Column, in characters, at which the error was detected, starting at 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl Error for GqlParseError {}
+
+impl Display for GqlParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.column
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    QuotedName(String),
+    String(String),
+    /// The magnitude of an integer literal; a leading `-` is a separate token.
+    Integer(u64),
+    Double(f64),
+    NamedBinding(String),
+    PositionalBinding(usize),
+    Star,
+    Comma,
+    LeftParen,
+    RightParen,
+    Plus,
+    Minus,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    End,
+}
+
+struct Spanned {
+    token: Token,
+    start: usize,
+    end: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Spanned>, GqlParseError> {
+    let bytes = input.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let c = bytes[i];
+
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let token = match c {
+            b'*' => single(&mut i, Token::Star),
+            b',' => single(&mut i, Token::Comma),
+            b'(' => single(&mut i, Token::LeftParen),
+            b')' => single(&mut i, Token::RightParen),
+            b'+' => single(&mut i, Token::Plus),
+            b'-' => single(&mut i, Token::Minus),
+            b'=' => single(&mut i, Token::Equal),
+            b'!' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                Token::NotEqual
+            }
+            b'<' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                Token::LessThanOrEqual
+            }
+            b'<' => single(&mut i, Token::LessThan),
+            b'>' if bytes.get(i + 1) == Some(&b'=') => {
+                i += 2;
+                Token::GreaterThanOrEqual
+            }
+            b'>' => single(&mut i, Token::GreaterThan),
+            b'\'' | b'"' => Token::String(quoted(input, &mut i)?),
+            b'`' => Token::QuotedName(quoted(input, &mut i)?),
+            b'@' => {
+                i += 1;
+                let name_start = i;
+                while i < bytes.len() && is_name_byte(bytes[i]) {
+                    i += 1;
+                }
+                let name = &input[name_start..i];
+                if name.is_empty() {
+                    return Err(GqlParseError::new(
+                        input,
+                        start,
+                        "Expected a parameter name or position after '@'".to_string(),
+                    ));
+                }
+                if name.bytes().all(|b| b.is_ascii_digit()) {
+                    let position = name.parse().map_err(|_| {
+                        GqlParseError::new(input, start, format!("Invalid parameter '@{name}'"))
+                    })?;
+                    Token::PositionalBinding(position)
+                } else {
+                    Token::NamedBinding(name.to_string())
+                }
+            }
+            c if c.is_ascii_digit() || (c == b'.' && next_is_digit(bytes, i + 1)) => {
+                number(input, &mut i)?
+            }
+            c if is_name_byte(c) => {
+                while i < bytes.len() && is_name_byte(bytes[i]) {
+                    i += 1;
+                }
+                Token::Name(input[start..i].to_string())
+            }
+            _ => {
+                let c = input[start..].chars().next().unwrap_or_default();
+                return Err(GqlParseError::new(
+                    input,
+                    start,
+                    format!("Unexpected character '{c}'"),
+                ));
+            }
+        };
+
+        tokens.push(Spanned {
+            token,
+            start,
+            end: i,
+        });
+    }
+
+    tokens.push(Spanned {
+        token: Token::End,
+        start: input.len(),
+        end: input.len(),
+    });
+    Ok(tokens)
+}
+
+fn single(i: &mut usize, token: Token) -> Token {
+    *i += 1;
+    token
+}
+
+fn is_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || !b.is_ascii()
+}
+
+fn next_is_digit(bytes: &[u8], i: usize) -> bool {
+    bytes.get(i).is_some_and(u8::is_ascii_digit)
+}
+
+/// Read a string or name delimited by the quote at `i`. The quote is escaped by doubling it or with
+/// a backslash, and backslash escapes are supported for common control characters.
+fn quoted(input: &str, i: &mut usize) -> Result<String, GqlParseError> {
+    let start = *i;
+    let quote = input.as_bytes()[start] as char;
+    let mut value = String::new();
+    let mut chars = input[start + 1..].char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            c if c == quote => {
+                if chars.peek().map(|(_, next)| *next) == Some(quote) {
+                    chars.next();
+                    value.push(quote);
+                } else {
+                    *i = start + 1 + offset + c.len_utf8();
+                    return Ok(value);
+                }
+            }
+            '\\' => {
+                let Some((_, escaped)) = chars.next() else {
+                    break;
+                };
+                value.push(match escaped {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    other => other,
+                });
+            }
+            c => value.push(c),
+        }
+    }
+
+    let what = if quote == '`' { "name" } else { "string" };
+    Err(GqlParseError::new(
+        input,
+        start,
+        format!("Unterminated quoted {what}"),
+    ))
+}
+
+fn number(input: &str, i: &mut usize) -> Result<Token, GqlParseError> {
+    let bytes = input.as_bytes();
+    let start = *i;
+    let mut is_double = false;
+
+    while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+        *i += 1;
+    }
+    if *i < bytes.len() && bytes[*i] == b'.' {
+        is_double = true;
+        *i += 1;
+        while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+            *i += 1;
+        }
+    }
+    if *i < bytes.len() && (bytes[*i] == b'e' || bytes[*i] == b'E') {
+        is_double = true;
+        *i += 1;
+        if *i < bytes.len() && (bytes[*i] == b'+' || bytes[*i] == b'-') {
+            *i += 1;
+        }
+        while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+            *i += 1;
+        }
+    }
+    if *i < bytes.len() && is_name_byte(bytes[*i]) {
+        // Include the whole offending character, which may be longer than one byte.
+        let end = *i + input[*i..].chars().next().map_or(1, char::len_utf8);
+        return Err(GqlParseError::new(
+            input,
+            start,
+            format!("Invalid number '{}'", &input[start..end]),
+        ));
+    }
+
+    let text = &input[start..*i];
+    let token = if is_double {
+        text.parse().map(Token::Double).ok()
+    } else {
+        text.parse().map(Token::Integer).ok()
+    };
+    token.ok_or_else(|| GqlParseError::new(input, start, format!("Invalid number '{text}'")))
+}
+
+/// The operand of a `LIMIT` or `OFFSET` clause.
+enum Operand {
+    Integer(i32),
+    Cursor(Vec<u8>),
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Spanned>,
+    position: usize,
+    bindings: Option<&'a GqlQuery>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str, bindings: Option<&'a GqlQuery>) -> Result<Self, GqlParseError> {
+        Ok(Parser {
+            input,
+            tokens: tokenize(input)?,
+            position: 0,
+            bindings,
+        })
+    }
+
+    fn parse(mut self) -> Result<Query, GqlParseError> {
+        let mut query = Query::default();
+
+        self.expect_keyword("SELECT")?;
+        self.parse_select(&mut query)?;
+
+        if self.accept_keyword("FROM") {
+            query.kind = vec![KindExpression {
+                name: self.parse_name()?,
+            }];
+        }
+
+        if self.accept_keyword("WHERE") {
+            query.filter = Some(self.parse_or()?);
+        }
+
+        if self.accept_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                let name = self.parse_name()?;
+                let direction = if self.accept_keyword("DESC") {
+                    Direction::Descending
+                } else {
+                    self.accept_keyword("ASC");
+                    Direction::Ascending
+                };
+                query.order.push(PropertyOrder {
+                    property: Some(PropertyReference { name }),
+                    direction: direction as i32,
+                });
+                if !self.accept(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+
+        if self.accept_keyword("LIMIT") {
+            let first = self.parse_operand()?;
+            let count = if self.accept(&Token::Comma) {
+                self.apply_offset(&mut query, first);
+                self.parse_operand()?
+            } else {
+                first
+            };
+            match count {
+                Operand::Integer(limit) => query.limit = Some(limit),
+                Operand::Cursor(cursor) => query.end_cursor = cursor,
+            }
+        }
+
+        if self.accept_keyword("OFFSET") {
+            let offset = self.parse_operand()?;
+            let is_cursor = matches!(offset, Operand::Cursor(_));
+            self.apply_offset(&mut query, offset);
+            if is_cursor && self.accept(&Token::Plus) {
+                match self.parse_operand()? {
+                    Operand::Integer(offset) => query.offset = offset,
+                    Operand::Cursor(_) => {
+                        return Err(self.error_at_previous("Expected an integer offset"))
+                    }
+                }
+            }
+        }
+
+        if self.peek() != &Token::End {
+            return Err(self.unexpected("FROM, WHERE, ORDER BY, LIMIT, OFFSET or end of query"));
+        }
+
+        Ok(query)
+    }
+
+    fn parse_select(&mut self, query: &mut Query) -> Result<(), GqlParseError> {
+        if self.accept_keyword("DISTINCT") {
+            if self.accept_keyword("ON") {
+                self.expect(&Token::LeftParen, "'('")?;
+                query.distinct_on = self
+                    .parse_name_list()?
+                    .into_iter()
+                    .map(|name| PropertyReference { name })
+                    .collect();
+                self.expect(&Token::RightParen, "')'")?;
+            } else {
+                let names = self.parse_name_list()?;
+                query.distinct_on = names
+                    .iter()
+                    .map(|name| PropertyReference { name: name.clone() })
+                    .collect();
+                query.projection = projection(names);
+                return Ok(());
+            }
+        }
+
+        if !self.accept(&Token::Star) {
+            query.projection = projection(self.parse_name_list()?);
+        }
+        Ok(())
+    }
+
+    fn parse_name_list(&mut self) -> Result<Vec<String>, GqlParseError> {
+        let mut names = vec![self.parse_name()?];
+        while self.accept(&Token::Comma) {
+            names.push(self.parse_name()?);
+        }
+        Ok(names)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, GqlParseError> {
+        let mut filters = vec![self.parse_and()?];
+        while self.accept_keyword("OR") {
+            filters.push(self.parse_and()?);
+        }
+        Ok(composite(filters, Filter::or))
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, GqlParseError> {
+        let mut filters = vec![self.parse_primary()?];
+        while self.accept_keyword("AND") {
+            filters.push(self.parse_primary()?);
+        }
+        Ok(composite(filters, Filter::and))
+    }
+
+    fn parse_primary(&mut self) -> Result<Filter, GqlParseError> {
+        if self.accept(&Token::LeftParen) {
+            let filter = self.parse_or()?;
+            self.expect(&Token::RightParen, "')'")?;
+            return Ok(filter);
+        }
+
+        if !self.peek_is_name() {
+            // <value> IN <property> or <value> HAS DESCENDANT <property>
+            let value = self.parse_value()?;
+            if self.accept_keyword("IN") {
+                let name = self.parse_name()?;
+                return Ok(Filter::property(name, Operator::Equal, value));
+            }
+            if self.accept_keyword("HAS") {
+                self.expect_keyword("DESCENDANT")?;
+                let name = self.parse_name()?;
+                return Ok(Filter::property(name, Operator::HasAncestor, value));
+            }
+            return Err(self.unexpected("IN or HAS DESCENDANT"));
+        }
+
+        let name = self.parse_name()?;
+        let op = match self.peek().clone() {
+            Token::Equal => Operator::Equal,
+            Token::NotEqual => Operator::NotEqual,
+            Token::LessThan => Operator::LessThan,
+            Token::LessThanOrEqual => Operator::LessThanOrEqual,
+            Token::GreaterThan => Operator::GreaterThan,
+            Token::GreaterThanOrEqual => Operator::GreaterThanOrEqual,
+            _ if self.accept_keyword("IN") => {
+                return Ok(Filter::property(name, Operator::In, self.parse_value()?))
+            }
+            _ if self.accept_keyword("NOT") => {
+                self.expect_keyword("IN")?;
+                return Ok(Filter::property(name, Operator::NotIn, self.parse_value()?));
+            }
+            _ if self.accept_keyword("CONTAINS") => {
+                return Ok(Filter::property(name, Operator::Equal, self.parse_value()?))
+            }
+            _ if self.accept_keyword("HAS") => {
+                self.expect_keyword("ANCESTOR")?;
+                let value = self.parse_value()?;
+                return Ok(Filter::property(name, Operator::HasAncestor, value));
+            }
+            _ if self.accept_keyword("IS") => {
+                let start = self.tokens[self.position].start;
+                self.expect_keyword("NULL")?;
+                self.check_literal(start)?;
+                return Ok(Filter::property(
+                    name,
+                    Operator::Equal,
+                    ValueType::NullValue(0),
+                ));
+            }
+            _ => {
+                return Err(self.unexpected(
+                    "a comparison operator, IN, NOT IN, CONTAINS, HAS ANCESTOR or IS NULL",
+                ))
+            }
+        };
+        self.position += 1;
+        Ok(Filter::property(name, op, self.parse_value()?))
+    }
+
+    fn parse_value(&mut self) -> Result<ValueType, GqlParseError> {
+        let start = self.tokens[self.position].start;
+
+        if let Some(parameter) = self.accept_binding()? {
+            return match parameter {
+                ParameterType::Value(Value {
+                    value_type: Some(value_type),
+                    ..
+                }) => Ok(value_type),
+                ParameterType::Value(_) => Ok(ValueType::NullValue(0)),
+                ParameterType::Cursor(_) => Err(GqlParseError::new(
+                    self.input,
+                    start,
+                    "Cursors can only be bound in LIMIT and OFFSET".to_string(),
+                )),
+            };
+        }
+
+        let position = self.position;
+        let value = match self.next().clone() {
+            Token::String(s) => ValueType::StringValue(s),
+            Token::Integer(i) => ValueType::IntegerValue(self.to_int(i, start)?),
+            Token::Double(d) => ValueType::DoubleValue(d),
+            Token::Minus => match self.next().clone() {
+                // Negate the magnitude so that i64::MIN, whose magnitude is not an i64, is valid.
+                Token::Integer(i) => match 0i64.checked_sub_unsigned(i) {
+                    Some(i) => ValueType::IntegerValue(i),
+                    None => {
+                        return Err(GqlParseError::new(
+                            self.input,
+                            start,
+                            format!("Value -{i} is out of range"),
+                        ))
+                    }
+                },
+                Token::Double(d) => ValueType::DoubleValue(-d),
+                _ => return Err(self.error_at_previous("Expected a number after '-'")),
+            },
+            Token::Name(name) if name.eq_ignore_ascii_case("TRUE") => ValueType::BooleanValue(true),
+            Token::Name(name) if name.eq_ignore_ascii_case("FALSE") => {
+                ValueType::BooleanValue(false)
+            }
+            Token::Name(name) if name.eq_ignore_ascii_case("NULL") => ValueType::NullValue(0),
+            Token::Name(name) if name.eq_ignore_ascii_case("KEY") => {
+                ValueType::KeyValue(self.parse_key()?)
+            }
+            Token::Name(name) if name.eq_ignore_ascii_case("DATETIME") => {
+                self.expect(&Token::LeftParen, "'('")?;
+                let text = self.parse_string()?;
+                let timestamp = text.parse::<Timestamp>().map_err(|e| {
+                    self.error_at_previous(&format!("Invalid DATETIME '{text}': {e}"))
+                })?;
+                self.expect(&Token::RightParen, "')'")?;
+                ValueType::TimestampValue(timestamp)
+            }
+            Token::Name(name) if name.eq_ignore_ascii_case("BLOB") => {
+                self.expect(&Token::LeftParen, "'('")?;
+                let text = self.parse_string()?;
+                let blob = BLOB_ENGINE
+                    .decode(&text)
+                    .map_err(|e| self.error_at_previous(&format!("Invalid BLOB '{text}': {e}")))?;
+                self.expect(&Token::RightParen, "')'")?;
+                ValueType::BlobValue(blob)
+            }
+            Token::Name(name) if name.eq_ignore_ascii_case("ARRAY") => {
+                self.expect(&Token::LeftParen, "'('")?;
+                let mut values = vec![];
+                if !self.accept(&Token::RightParen) {
+                    loop {
+                        values.push(Value {
+                            value_type: Some(self.parse_value()?),
+                            ..Default::default()
+                        });
+                        if !self.accept(&Token::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect(&Token::RightParen, "')'")?;
+                }
+                // The elements of the array were checked individually.
+                return Ok(ValueType::ArrayValue(ArrayValue { values }));
+            }
+            _ => {
+                self.position = position;
+                return Err(self.unexpected("a value"));
+            }
+        };
+
+        self.check_literal(start)?;
+        Ok(value)
+    }
+
+    fn parse_key(&mut self) -> Result<Key, GqlParseError> {
+        self.expect(&Token::LeftParen, "'('")?;
+        let mut key = Key::default();
+
+        if self.accept_keyword("PROJECT") {
+            self.expect(&Token::LeftParen, "'('")?;
+            let project_id = self.parse_string()?;
+            self.expect(&Token::RightParen, "')'")?;
+            self.expect(&Token::Comma, "','")?;
+            key.partition_id
+                .get_or_insert_with(PartitionId::default)
+                .project_id = project_id;
+        }
+
+        if self.accept_keyword("NAMESPACE") {
+            self.expect(&Token::LeftParen, "'('")?;
+            let namespace_id = self.parse_string()?;
+            self.expect(&Token::RightParen, "')'")?;
+            self.expect(&Token::Comma, "','")?;
+            key.partition_id
+                .get_or_insert_with(PartitionId::default)
+                .namespace_id = namespace_id;
+        }
+
+        loop {
+            let kind = match self.peek().clone() {
+                Token::String(kind) => {
+                    self.position += 1;
+                    kind
+                }
+                _ => self.parse_name()?,
+            };
+            self.expect(&Token::Comma, "','")?;
+            let id_type = match self.peek().clone() {
+                Token::Integer(id) => {
+                    IdType::Id(self.to_int(id, self.tokens[self.position].start)?)
+                }
+                Token::String(name) => IdType::Name(name),
+                _ => return Err(self.unexpected("an integer id or string name")),
+            };
+            self.position += 1;
+            key.path.push(PathElement {
+                kind,
+                id_type: Some(id_type),
+            });
+            if !self.accept(&Token::Comma) {
+                break;
+            }
+        }
+
+        self.expect(&Token::RightParen, "')'")?;
+        Ok(key)
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, GqlParseError> {
+        let start = self.tokens[self.position].start;
+
+        if let Some(parameter) = self.accept_binding()? {
+            return match parameter {
+                ParameterType::Cursor(cursor) => Ok(Operand::Cursor(cursor)),
+                ParameterType::Value(Value {
+                    value_type: Some(ValueType::IntegerValue(i)),
+                    ..
+                }) => self.to_int(i, start).map(Operand::Integer),
+                ParameterType::Value(_) => Err(GqlParseError::new(
+                    self.input,
+                    start,
+                    "Expected an integer or cursor binding".to_string(),
+                )),
+            };
+        }
+
+        match self.peek().clone() {
+            Token::Integer(i) => {
+                self.position += 1;
+                self.check_literal(start)?;
+                self.to_int(i, start).map(Operand::Integer)
+            }
+            _ => Err(self.unexpected("an integer or binding")),
+        }
+    }
+
+    fn apply_offset(&self, query: &mut Query, offset: Operand) {
+        match offset {
+            Operand::Integer(offset) => query.offset = offset,
+            Operand::Cursor(cursor) => query.start_cursor = cursor,
+        }
+    }
+
+    fn to_int<I: Display + Copy, T: TryFrom<I>>(
+        &self,
+        i: I,
+        start: usize,
+    ) -> Result<T, GqlParseError> {
+        T::try_from(i).map_err(|_| {
+            GqlParseError::new(self.input, start, format!("Value {i} is out of range"))
+        })
+    }
+
+    /// Resolve a binding site, if the next token is one.
+    fn accept_binding(&mut self) -> Result<Option<ParameterType>, GqlParseError> {
+        let start = self.tokens[self.position].start;
+        let parameter = match self.peek().clone() {
+            Token::NamedBinding(name) => self
+                .bindings
+                .and_then(|b| b.named_bindings.get(&name))
+                .ok_or_else(|| format!("No binding for parameter '@{name}'")),
+            Token::PositionalBinding(position) => self
+                .bindings
+                .and_then(|b| b.positional_bindings.get(position.checked_sub(1)?))
+                .ok_or_else(|| format!("No binding for parameter '@{position}'")),
+            _ => return Ok(None),
+        };
+        let parameter =
+            parameter.map_err(|message| GqlParseError::new(self.input, start, message))?;
+        self.position += 1;
+
+        match parameter {
+            GqlQueryParameter {
+                parameter_type: Some(parameter_type),
+            } => Ok(Some(parameter_type.clone())),
+            GqlQueryParameter {
+                parameter_type: None,
+            } => Err(GqlParseError::new(
+                self.input,
+                start,
+                "Binding has no value".to_string(),
+            )),
+        }
+    }
+
+    fn check_literal(&self, start: usize) -> Result<(), GqlParseError> {
+        match self.bindings {
+            Some(gql) if !gql.allow_literals => Err(GqlParseError::new(
+                self.input,
+                start,
+                "Literal values are not allowed, use a binding or set allow_literals".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<String, GqlParseError> {
+        match self.peek().clone() {
+            Token::QuotedName(name) => {
+                self.position += 1;
+                Ok(name)
+            }
+            Token::Name(name) if !is_keyword(&name) => {
+                self.position += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, GqlParseError> {
+        match self.peek().clone() {
+            Token::String(s) => {
+                self.position += 1;
+                Ok(s)
+            }
+            _ => Err(self.unexpected("a string")),
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].token
+    }
+
+    fn peek_is_name(&self) -> bool {
+        match self.peek() {
+            Token::QuotedName(_) => true,
+            Token::Name(name) => !is_keyword(name),
+            _ => false,
+        }
+    }
+
+    fn next(&mut self) -> &Token {
+        let token = &self.tokens[self.position].token;
+        if *token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn accept(&mut self, token: &Token) -> bool {
+        if self.peek() == token {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Token::Name(name) if name.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, token: &Token, expected: &str) -> Result<(), GqlParseError> {
+        if self.accept(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), GqlParseError> {
+        if self.accept_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(keyword))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> GqlParseError {
+        let Spanned { token, start, end } = &self.tokens[self.position];
+        let found = match token {
+            Token::End => "end of query".to_string(),
+            _ => format!("'{}'", &self.input[*start..*end]),
+        };
+        GqlParseError::new(
+            self.input,
+            *start,
+            format!("Expected {expected}, found {found}"),
+        )
+    }
+
+    fn error_at_previous(&self, message: &str) -> GqlParseError {
+        let start = self.tokens[self.position.saturating_sub(1)].start;
+        GqlParseError::new(self.input, start, message.to_string())
+    }
+}
+
+pub(super) fn is_keyword(name: &str) -> bool {
+    KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(name))
+}
+
+fn projection(names: Vec<String>) -> Vec<Projection> {
+    names
+        .into_iter()
+        .map(|name| Projection {
+            property: Some(PropertyReference { name }),
+        })
+        .collect()
+}
+
+fn composite(mut filters: Vec<Filter>, combine: fn(Vec<Filter>) -> Filter) -> Filter {
+    if filters.len() == 1 {
+        filters.remove(0)
+    } else {
+        combine(filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn parse(gql: &str) -> Query {
+        Query::from_gql(gql).unwrap_or_else(|e| panic!("{gql}: {e}"))
+    }
+
+    fn parse_err(gql: &str) -> GqlParseError {
+        Query::from_gql(gql).expect_err(gql)
+    }
+
+    fn filter(gql: &str) -> Filter {
+        parse(&format!("SELECT * FROM A WHERE {gql}"))
+            .filter
+            .unwrap()
+    }
+
+    fn value(value_type: ValueType) -> GqlQueryParameter {
+        GqlQueryParameter {
+            parameter_type: Some(ParameterType::Value(Value {
+                value_type: Some(value_type),
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn cursor(cursor: &[u8]) -> GqlQueryParameter {
+        GqlQueryParameter {
+            parameter_type: Some(ParameterType::Cursor(cursor.to_vec())),
+        }
+    }
+
+    fn names(references: &[PropertyReference]) -> Vec<&str> {
+        references.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    fn projected(query: &Query) -> Vec<&str> {
+        query
+            .projection
+            .iter()
+            .map(|p| p.property.as_ref().unwrap().name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn select() {
+        let query = parse("SELECT * FROM Book");
+        assert_eq!(query.kind[0].name, "Book");
+        assert!(query.projection.is_empty());
+        assert!(query.distinct_on.is_empty());
+
+        let query = parse("SELECT title, `year` FROM Book");
+        assert_eq!(projected(&query), ["title", "year"]);
+        assert!(query.distinct_on.is_empty());
+
+        let query = parse("select * from `Book Shelf`");
+        assert_eq!(query.kind[0].name, "Book Shelf");
+
+        let query = parse("SELECT *");
+        assert!(query.kind.is_empty());
+    }
+
+    #[test]
+    fn select_distinct() {
+        let query = parse("SELECT DISTINCT author, year FROM Book");
+        assert_eq!(projected(&query), ["author", "year"]);
+        assert_eq!(names(&query.distinct_on), ["author", "year"]);
+
+        let query = parse("SELECT DISTINCT ON (author) title, author FROM Book");
+        assert_eq!(projected(&query), ["title", "author"]);
+        assert_eq!(names(&query.distinct_on), ["author"]);
+
+        let query = parse("SELECT DISTINCT ON (author) * FROM Book");
+        assert!(query.projection.is_empty());
+        assert_eq!(names(&query.distinct_on), ["author"]);
+    }
+
+    #[test]
+    fn where_nested_and_or() {
+        let a = Filter::property("a", Operator::Equal, ValueType::IntegerValue(1));
+        let b = Filter::property("b", Operator::GreaterThan, ValueType::IntegerValue(2));
+        let c = Filter::property("c", Operator::LessThan, ValueType::IntegerValue(3));
+        let d = Filter::property("d", Operator::NotEqual, ValueType::IntegerValue(4));
+
+        // AND binds tighter than OR.
+        assert_eq!(
+            filter("a = 1 AND b > 2 OR c < 3"),
+            Filter::or(vec![Filter::and(vec![a.clone(), b.clone()]), c.clone()])
+        );
+        assert_eq!(
+            filter("a = 1 AND (b > 2 OR c < 3 AND d != 4)"),
+            Filter::and(vec![
+                a.clone(),
+                Filter::or(vec![b.clone(), Filter::and(vec![c.clone(), d.clone()])]),
+            ])
+        );
+        assert_eq!(
+            filter("(a = 1 OR b > 2) AND (c < 3 OR d != 4)"),
+            Filter::and(vec![
+                Filter::or(vec![a.clone(), b.clone()]),
+                Filter::or(vec![c, d]),
+            ])
+        );
+        assert_eq!(filter("((a = 1))"), a);
+    }
+
+    #[test]
+    fn where_values() {
+        assert_eq!(
+            filter("a <= -1.5 AND b >= 'it''s' AND c = TRUE AND d IS NULL"),
+            Filter::and(vec![
+                Filter::property("a", Operator::LessThanOrEqual, ValueType::DoubleValue(-1.5)),
+                Filter::property(
+                    "b",
+                    Operator::GreaterThanOrEqual,
+                    ValueType::StringValue("it's".to_string())
+                ),
+                Filter::property("c", Operator::Equal, ValueType::BooleanValue(true)),
+                Filter::property("d", Operator::Equal, ValueType::NullValue(0)),
+            ])
+        );
+        assert_eq!(
+            filter("a = BLOB('AQID')"),
+            Filter::property("a", Operator::Equal, ValueType::BlobValue(vec![1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn in_and_not_in() {
+        let array = ValueType::ArrayValue(ArrayValue {
+            values: vec![
+                Value {
+                    value_type: Some(ValueType::IntegerValue(1)),
+                    ..Default::default()
+                },
+                Value {
+                    value_type: Some(ValueType::StringValue("two".to_string())),
+                    ..Default::default()
+                },
+            ],
+        });
+        assert_eq!(
+            filter("a IN ARRAY(1, 'two')"),
+            Filter::property("a", Operator::In, array.clone())
+        );
+        assert_eq!(
+            filter("a NOT IN ARRAY(1, 'two')"),
+            Filter::property("a", Operator::NotIn, array)
+        );
+        // `<value> IN <property>` is an equality on a multi-valued property.
+        assert_eq!(
+            filter("'fantasy' IN tags"),
+            Filter::property(
+                "tags",
+                Operator::Equal,
+                ValueType::StringValue("fantasy".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn key_literals() {
+        let key = Key::builder()
+            .name("Author", "tolkien")
+            .id("Book", 12)
+            .build();
+        assert_eq!(
+            filter("__key__ HAS ANCESTOR KEY(Author, 'tolkien', Book, 12)"),
+            Filter::property(
+                "__key__",
+                Operator::HasAncestor,
+                ValueType::KeyValue(key.clone())
+            )
+        );
+        assert_eq!(
+            filter("KEY(Author, 'tolkien', Book, 12) HAS DESCENDANT __key__"),
+            Filter::property("__key__", Operator::HasAncestor, ValueType::KeyValue(key))
+        );
+
+        let key = Key::builder()
+            .project("library")
+            .namespace("fiction")
+            .name("Book Shelf", "top")
+            .build();
+        assert_eq!(
+            filter("shelf = KEY(PROJECT('library'), NAMESPACE('fiction'), 'Book Shelf', 'top')"),
+            Filter::property("shelf", Operator::Equal, ValueType::KeyValue(key))
+        );
+
+        let key = Key::builder().namespace("fiction").id("Book", 1).build();
+        assert_eq!(
+            filter("book = KEY(NAMESPACE('fiction'), Book, 1)"),
+            Filter::property("book", Operator::Equal, ValueType::KeyValue(key))
+        );
+
+        assert!(Query::from_gql("SELECT * WHERE k = KEY(Book)").is_err());
+        assert!(
+            Query::from_gql("SELECT * WHERE k = KEY(NAMESPACE('a'), PROJECT('b'), A, 1)").is_err()
+        );
+    }
+
+    #[test]
+    fn datetime() {
+        assert_eq!(
+            filter("published > DATETIME('2021-03-04T05:06:07.5Z')"),
+            Filter::property(
+                "published",
+                Operator::GreaterThan,
+                ValueType::TimestampValue(Timestamp {
+                    seconds: 1_614_834_367,
+                    nanos: 500_000_000,
+                })
+            )
+        );
+
+        let error = parse_err("SELECT * WHERE a = DATETIME('yesterday')");
+        assert!(error.message().starts_with("Invalid DATETIME 'yesterday'"));
+        assert_eq!(error.column(), 29);
+    }
+
+    #[test]
+    fn order_by() {
+        let query = parse("SELECT * FROM Book ORDER BY year DESC, title ASC, `author name`");
+        let order: Vec<_> = query
+            .order
+            .iter()
+            .map(|o| (o.property.as_ref().unwrap().name.as_str(), o.direction))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("year", Direction::Descending as i32),
+                ("title", Direction::Ascending as i32),
+                ("author name", Direction::Ascending as i32),
+            ]
+        );
+    }
+
+    #[test]
+    fn limit_and_offset() {
+        let query = parse("SELECT * FROM Book LIMIT 10");
+        assert_eq!((query.limit, query.offset), (Some(10), 0));
+
+        let query = parse("SELECT * FROM Book LIMIT 5, 10");
+        assert_eq!((query.limit, query.offset), (Some(10), 5));
+
+        let query = parse("SELECT * FROM Book LIMIT 10 OFFSET 20");
+        assert_eq!((query.limit, query.offset), (Some(10), 20));
+
+        assert!(Query::from_gql("SELECT * LIMIT 3000000000").is_err());
+    }
+
+    #[test]
+    fn cursor_bindings() {
+        let gql = GqlQuery {
+            query_string: "SELECT * FROM Book LIMIT @end OFFSET @start + 5".to_string(),
+            named_bindings: HashMap::from([
+                ("start".to_string(), cursor(b"start")),
+                ("end".to_string(), cursor(b"end")),
+            ]),
+            allow_literals: true,
+            ..Default::default()
+        };
+        let query = Query::from_gql_query(&gql).unwrap();
+        assert_eq!(query.start_cursor, b"start");
+        assert_eq!(query.end_cursor, b"end");
+        assert_eq!(query.offset, 5);
+        assert_eq!(query.limit, None);
+
+        let gql = GqlQuery {
+            query_string: "SELECT * FROM Book LIMIT @1, @2".to_string(),
+            positional_bindings: vec![cursor(b"start"), value(ValueType::IntegerValue(10))],
+            ..Default::default()
+        };
+        let query = Query::from_gql_query(&gql).unwrap();
+        assert_eq!(query.start_cursor, b"start");
+        assert_eq!(query.limit, Some(10));
+
+        let gql = GqlQuery {
+            query_string: "SELECT * WHERE a = @1".to_string(),
+            positional_bindings: vec![cursor(b"start")],
+            ..Default::default()
+        };
+        let error = Query::from_gql_query(&gql).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "Cursors can only be bound in LIMIT and OFFSET"
+        );
+    }
+
+    #[test]
+    fn value_bindings() {
+        let gql = GqlQuery {
+            query_string: "SELECT * FROM Book WHERE author = @author AND year > @1 AND tag IN @2"
+                .to_string(),
+            named_bindings: HashMap::from([(
+                "author".to_string(),
+                value(ValueType::StringValue("tolkien".to_string())),
+            )]),
+            positional_bindings: vec![
+                value(ValueType::IntegerValue(1950)),
+                value(ValueType::ArrayValue(ArrayValue::default())),
+            ],
+            ..Default::default()
+        };
+        let query = Query::from_gql_query(&gql).unwrap();
+        assert_eq!(
+            query.filter.unwrap(),
+            Filter::and(vec![
+                Filter::property(
+                    "author",
+                    Operator::Equal,
+                    ValueType::StringValue("tolkien".to_string())
+                ),
+                Filter::property("year", Operator::GreaterThan, ValueType::IntegerValue(1950)),
+                Filter::property(
+                    "tag",
+                    Operator::In,
+                    ValueType::ArrayValue(ArrayValue::default())
+                ),
+            ])
+        );
+
+        let missing = GqlQuery {
+            query_string: "SELECT * WHERE a = @2 AND b = @title".to_string(),
+            positional_bindings: vec![value(ValueType::IntegerValue(1))],
+            ..Default::default()
+        };
+        let error = Query::from_gql_query(&missing).unwrap_err();
+        assert_eq!(error.message(), "No binding for parameter '@2'");
+        assert_eq!(error.column(), 20);
+
+        let error = Query::from_gql("SELECT * WHERE a = @0").unwrap_err();
+        assert_eq!(error.message(), "No binding for parameter '@0'");
+    }
+
+    #[test]
+    fn literals_rejected_without_allow_literals() {
+        let rejected = [
+            ("SELECT * WHERE a = 1", 20),
+            ("SELECT * WHERE a = 'x'", 20),
+            ("SELECT * WHERE a IS NULL", 21),
+            ("SELECT * WHERE a = KEY(A, 1)", 20),
+            ("SELECT * WHERE a IN ARRAY(@1, 2)", 31),
+            ("SELECT * LIMIT 10", 16),
+            ("SELECT * LIMIT @1 OFFSET 10", 26),
+        ];
+        for (query_string, column) in rejected {
+            let gql = GqlQuery {
+                query_string: query_string.to_string(),
+                positional_bindings: vec![value(ValueType::IntegerValue(1))],
+                ..Default::default()
+            };
+            let error = Query::from_gql_query(&gql).expect_err(query_string);
+            assert_eq!(
+                error.message(),
+                "Literal values are not allowed, use a binding or set allow_literals"
+            );
+            assert_eq!(error.column(), column, "{query_string}");
+
+            let allowed = GqlQuery {
+                allow_literals: true,
+                ..gql
+            };
+            assert!(Query::from_gql_query(&allowed).is_ok(), "{query_string}");
+        }
+    }
+
+    #[test]
+    fn error_positions() {
+        let error = parse_err("SELECT * FROM Book\nWHERE a = 1\n  ORDER year");
+        assert_eq!(error.message(), "Expected BY, found 'year'");
+        assert_eq!((error.line(), error.column()), (3, 9));
+        assert_eq!(error.offset(), 39);
+        assert_eq!(
+            error.to_string(),
+            "Expected BY, found 'year' at line 3, column 9"
+        );
+
+        let error = parse_err("SELECT * FROM Book WHERE");
+        assert_eq!(error.message(), "Expected a value, found end of query");
+        assert_eq!((error.line(), error.column()), (1, 25));
+
+        // Columns count characters, not bytes.
+        let error = parse_err("SELECT * FROM Bücher WHERE a = 'é' b");
+        assert_eq!((error.line(), error.column()), (1, 36));
+
+        let error = parse_err("SELECT * WHERE a = 'open\n");
+        assert_eq!(error.message(), "Unterminated quoted string");
+        assert_eq!((error.line(), error.column()), (1, 20));
+
+        let error = parse_err("SELECT * WHERE a ~ 1");
+        assert_eq!(error.message(), "Unexpected character '~'");
+        assert_eq!(error.column(), 18);
+    }
+
+    #[test]
+    fn integer_bounds() {
+        assert_eq!(
+            filter("a = -9223372036854775808 AND b = 9223372036854775807"),
+            Filter::and(vec![
+                Filter::property("a", Operator::Equal, ValueType::IntegerValue(i64::MIN)),
+                Filter::property("b", Operator::Equal, ValueType::IntegerValue(i64::MAX)),
+            ])
+        );
+
+        let error = parse_err("SELECT * WHERE a = 9223372036854775808");
+        assert_eq!(error.message(), "Value 9223372036854775808 is out of range");
+        assert_eq!(error.column(), 20);
+
+        let error = parse_err("SELECT * WHERE a = -9223372036854775809");
+        assert_eq!(
+            error.message(),
+            "Value -9223372036854775809 is out of range"
+        );
+        assert_eq!(error.column(), 20);
+
+        let error = parse_err("SELECT * WHERE k = KEY(A, 9223372036854775808)");
+        assert_eq!(error.message(), "Value 9223372036854775808 is out of range");
+        assert_eq!(error.column(), 27);
+    }
+
+    #[test]
+    fn numbers_followed_by_non_ascii() {
+        for (gql, number) in [
+            ("SELECT * FROM A WHERE a = 1é", "1é"),
+            ("SELECT * FROM A WHERE a = 1ü", "1ü"),
+            ("SELECT * FROM A WHERE a = 1.5é", "1.5é"),
+            ("SELECT * FROM A WHERE a = 1€", "1€"),
+            ("SELECT * FROM A WHERE a = 1e5😀", "1e5😀"),
+        ] {
+            let error = parse_err(gql);
+            assert_eq!(error.message(), format!("Invalid number '{number}'"));
+            assert_eq!(error.column(), 27);
+        }
+
+        assert_eq!(
+            filter("a = 1e999"),
+            Filter::property("a", Operator::Equal, ValueType::DoubleValue(f64::INFINITY))
+        );
+        assert!(Query::from_gql("SELECT * WHERE a = 99999999999999999999").is_err());
+    }
+}
@@ -0,0 +1,562 @@
+use std::fmt::{self, Display, Formatter, Write};
+
+use base64::{engine::general_purpose::URL_SAFE, Engine};
+
+use crate::google::datastore::v1::{
+    aggregation_query::{aggregation::Operator as AggregationOperator, QueryType},
+    composite_filter::Operator as CompositeOperator,
+    filter::FilterType,
+    key::path_element::IdType,
+    property_filter::Operator,
+    property_order::Direction,
+    value::ValueType,
+    AggregationQuery, Filter, Key, PropertyReference, Query, Value,
+};
+
+use super::parser::is_keyword;
+
+impl Query {
+    ///
+    /// Render the query as GQL.
+    ///
+    /// Cursors cannot be written as GQL literals, so they are rendered as the bindings
+    /// `@start_cursor` and `@end_cursor`. Nearest neighbour searches are not part of GQL and are
+    /// omitted.
+    ///
+    /// Values that have no GQL literal are rendered as markers that [`Query::from_gql`] rejects,
+    /// rather than as something that parses to a different query: entities as `ENTITY(...)`, geo
+    /// points as `GEOPT(lat, lng)`, infinite and NaN doubles as `DOUBLE('Infinity')`,
+    /// `DOUBLE('-Infinity')` and `DOUBLE('NaN')`, and the missing id of an incomplete key as
+    /// `?`, as in `KEY(Book, ?)`. Queries without such values parse back to the same query.
+    ///
+    pub fn to_gql(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for Query {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let projection: Vec<&str> = self
+            .projection
+            .iter()
+            .filter_map(|p| p.property.as_ref())
+            .map(|p| p.name.as_str())
+            .collect();
+        let distinct_on: Vec<&str> = self.distinct_on.iter().map(|p| p.name.as_str()).collect();
+
+        f.write_str("SELECT ")?;
+        if !distinct_on.is_empty() && distinct_on == projection {
+            f.write_str("DISTINCT ")?;
+        } else if !distinct_on.is_empty() {
+            f.write_str("DISTINCT ON (")?;
+            write_names(f, &distinct_on)?;
+            f.write_str(") ")?;
+        }
+        if projection.is_empty() {
+            f.write_char('*')?;
+        } else {
+            write_names(f, &projection)?;
+        }
+
+        if let Some(kind) = self.kind.first() {
+            f.write_str(" FROM ")?;
+            write_name(f, &kind.name)?;
+        }
+
+        if let Some(filter) = self.filter.as_ref().filter(|f| f.filter_type.is_some()) {
+            f.write_str(" WHERE ")?;
+            write_filter(f, filter, false)?;
+        }
+
+        for (i, order) in self.order.iter().enumerate() {
+            f.write_str(if i == 0 { " ORDER BY " } else { ", " })?;
+            write_property(f, order.property.as_ref())?;
+            if order.direction() == Direction::Descending {
+                f.write_str(" DESC")?;
+            }
+        }
+
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {limit}")?;
+        } else if !self.end_cursor.is_empty() {
+            f.write_str(" LIMIT @end_cursor")?;
+        }
+
+        match (self.start_cursor.is_empty(), self.offset) {
+            (true, 0) => {}
+            (true, offset) => write!(f, " OFFSET {offset}")?,
+            (false, 0) => f.write_str(" OFFSET @start_cursor")?,
+            (false, offset) => write!(f, " OFFSET @start_cursor + {offset}")?,
+        }
+
+        Ok(())
+    }
+}
+
+impl AggregationQuery {
+    /// Render the aggregation query as GQL. See [`Query::to_gql`].
+    pub fn to_gql(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for AggregationQuery {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("AGGREGATE ")?;
+        for (i, aggregation) in self.aggregations.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            match &aggregation.operator {
+                Some(AggregationOperator::Count(count)) => match count.up_to {
+                    Some(up_to) => write!(f, "COUNT_UP_TO({up_to})")?,
+                    None => f.write_str("COUNT(*)")?,
+                },
+                Some(AggregationOperator::Sum(sum)) => {
+                    f.write_str("SUM(")?;
+                    write_property(f, sum.property.as_ref())?;
+                    f.write_char(')')?;
+                }
+                Some(AggregationOperator::Avg(avg)) => {
+                    f.write_str("AVG(")?;
+                    write_property(f, avg.property.as_ref())?;
+                    f.write_char(')')?;
+                }
+                None => f.write_str("NULL")?,
+            }
+            if !aggregation.alias.is_empty() {
+                f.write_str(" AS ")?;
+                write_name(f, &aggregation.alias)?;
+            }
+        }
+
+        f.write_str(" OVER (")?;
+        if let Some(QueryType::NestedQuery(query)) = &self.query_type {
+            write!(f, "{query}")?;
+        }
+        f.write_char(')')
+    }
+}
+
+fn write_filter(f: &mut Formatter, filter: &Filter, nested: bool) -> fmt::Result {
+    match &filter.filter_type {
+        Some(FilterType::CompositeFilter(composite)) => {
+            let separator = match composite.op() {
+                CompositeOperator::Or => " OR ",
+                _ => " AND ",
+            };
+            if nested {
+                f.write_char('(')?;
+            }
+            for (i, filter) in composite.filters.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(separator)?;
+                }
+                write_filter(f, filter, true)?;
+            }
+            if nested {
+                f.write_char(')')?;
+            }
+            Ok(())
+        }
+        Some(FilterType::PropertyFilter(filter)) => {
+            write_property(f, filter.property.as_ref())?;
+            let op = match filter.op() {
+                Operator::Unspecified => " ? ",
+                Operator::LessThan => " < ",
+                Operator::LessThanOrEqual => " <= ",
+                Operator::GreaterThan => " > ",
+                Operator::GreaterThanOrEqual => " >= ",
+                Operator::Equal => " = ",
+                Operator::NotEqual => " != ",
+                Operator::In => " IN ",
+                Operator::NotIn => " NOT IN ",
+                Operator::HasAncestor => " HAS ANCESTOR ",
+            };
+            f.write_str(op)?;
+            write_value(f, filter.value.as_ref())
+        }
+        None => Ok(()),
+    }
+}
+
+fn write_value(f: &mut Formatter, value: Option<&Value>) -> fmt::Result {
+    let Some(value_type) = value.and_then(|v| v.value_type.as_ref()) else {
+        return f.write_str("NULL");
+    };
+
+    match value_type {
+        ValueType::NullValue(_) => f.write_str("NULL"),
+        ValueType::BooleanValue(true) => f.write_str("TRUE"),
+        ValueType::BooleanValue(false) => f.write_str("FALSE"),
+        ValueType::IntegerValue(i) => write!(f, "{i}"),
+        ValueType::DoubleValue(d) if d.is_nan() => f.write_str("DOUBLE('NaN')"),
+        ValueType::DoubleValue(d) if d.is_infinite() => {
+            let sign = if d.is_sign_negative() { "-" } else { "" };
+            write!(f, "DOUBLE('{sign}Infinity')")
+        }
+        ValueType::DoubleValue(d) => write!(f, "{d:?}"),
+        ValueType::TimestampValue(t) => {
+            f.write_str("DATETIME(")?;
+            write_string(f, &t.to_string())?;
+            f.write_char(')')
+        }
+        ValueType::KeyValue(key) => write_key(f, key),
+        ValueType::StringValue(s) => write_string(f, s),
+        ValueType::BlobValue(b) => {
+            f.write_str("BLOB(")?;
+            write_string(f, &URL_SAFE.encode(b))?;
+            f.write_char(')')
+        }
+        ValueType::GeoPointValue(p) => write!(f, "GEOPT({:?}, {:?})", p.latitude, p.longitude),
+        ValueType::EntityValue(_) => f.write_str("ENTITY(...)"),
+        ValueType::ArrayValue(array) => {
+            f.write_str("ARRAY(")?;
+            for (i, value) in array.values.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write_value(f, Some(value))?;
+            }
+            f.write_char(')')
+        }
+    }
+}
+
+fn write_key(f: &mut Formatter, key: &Key) -> fmt::Result {
+    f.write_str("KEY(")?;
+    let mut first = true;
+    let mut separator = |f: &mut Formatter| {
+        if std::mem::take(&mut first) {
+            Ok(())
+        } else {
+            f.write_str(", ")
+        }
+    };
+
+    if let Some(partition_id) = &key.partition_id {
+        if !partition_id.project_id.is_empty() {
+            separator(f)?;
+            f.write_str("PROJECT(")?;
+            write_string(f, &partition_id.project_id)?;
+            f.write_char(')')?;
+        }
+        if !partition_id.namespace_id.is_empty() {
+            separator(f)?;
+            f.write_str("NAMESPACE(")?;
+            write_string(f, &partition_id.namespace_id)?;
+            f.write_char(')')?;
+        }
+    }
+
+    for element in &key.path {
+        separator(f)?;
+        write_name(f, &element.kind)?;
+        match &element.id_type {
+            Some(IdType::Id(id)) => write!(f, ", {id}")?,
+            Some(IdType::Name(name)) => {
+                f.write_str(", ")?;
+                write_string(f, name)?;
+            }
+            None => f.write_str(", ?")?,
+        }
+    }
+
+    f.write_char(')')
+}
+
+fn write_property(f: &mut Formatter, property: Option<&PropertyReference>) -> fmt::Result {
+    write_name(f, property.map(|p| p.name.as_str()).unwrap_or_default())
+}
+
+fn write_names(f: &mut Formatter, names: &[&str]) -> fmt::Result {
+    for (i, name) in names.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write_name(f, name)?;
+    }
+    Ok(())
+}
+
+/// Write a property or kind name, quoting it with backticks unless it is a plain identifier.
+fn write_name(f: &mut Formatter, name: &str) -> fmt::Result {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        && !is_keyword(name);
+
+    if plain {
+        f.write_str(name)
+    } else {
+        write_quoted(f, name, '`')
+    }
+}
+
+fn write_string(f: &mut Formatter, s: &str) -> fmt::Result {
+    write_quoted(f, s, '\'')
+}
+
+fn write_quoted(f: &mut Formatter, s: &str, quote: char) -> fmt::Result {
+    f.write_char(quote)?;
+    for c in s.chars() {
+        match c {
+            c if c == quote => {
+                f.write_char(quote)?;
+                f.write_char(quote)?;
+            }
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char(quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use prost_types::Timestamp;
+
+    use crate::google::datastore::v1::{
+        gql_query_parameter::ParameterType, ArrayValue, GqlQuery, GqlQueryParameter,
+        KindExpression, Projection, PropertyOrder,
+    };
+
+    use super::*;
+
+    fn assert_round_trip(query: &Query) {
+        let gql = query.to_gql();
+        let parsed = Query::from_gql(&gql).unwrap_or_else(|e| panic!("{gql}: {e}"));
+        assert_eq!(&parsed, query, "{gql}");
+    }
+
+    fn query(filter: Filter) -> Query {
+        Query {
+            kind: vec![KindExpression {
+                name: "Book".to_string(),
+            }],
+            filter: Some(filter),
+            ..Default::default()
+        }
+    }
+
+    fn property(name: &str, op: Operator, value: ValueType) -> Filter {
+        Filter::property(name, op, value)
+    }
+
+    fn order(name: &str, direction: Direction) -> PropertyOrder {
+        PropertyOrder {
+            property: Some(PropertyReference {
+                name: name.to_string(),
+            }),
+            direction: direction as i32,
+        }
+    }
+
+    #[test]
+    fn round_trip_composite_filters() {
+        let a = property("a", Operator::Equal, ValueType::IntegerValue(1));
+        let b = property("b", Operator::GreaterThan, ValueType::DoubleValue(-2.5));
+        let c = property(
+            "c c",
+            Operator::LessThan,
+            ValueType::StringValue("it's".into()),
+        );
+        let d = property("select", Operator::NotIn, ValueType::NullValue(0));
+
+        for filter in [
+            Filter::and(vec![a.clone(), b.clone(), c.clone()]),
+            Filter::or(vec![a.clone(), Filter::and(vec![b.clone(), c.clone()])]),
+            Filter::and(vec![
+                Filter::or(vec![a.clone(), b.clone()]),
+                Filter::or(vec![c.clone(), d.clone()]),
+            ]),
+            Filter::or(vec![
+                Filter::and(vec![a.clone(), Filter::or(vec![b.clone(), c.clone()])]),
+                d.clone(),
+            ]),
+            Filter::and(vec![a.clone(), Filter::and(vec![b, c])]),
+        ] {
+            assert_round_trip(&query(filter));
+        }
+
+        let gql = "SELECT * FROM Book WHERE a = 1 AND (b > 2 OR c < 3)";
+        assert_eq!(Query::from_gql(gql).unwrap().to_gql(), gql);
+    }
+
+    #[test]
+    fn round_trip_values() {
+        let array = ValueType::ArrayValue(ArrayValue {
+            values: vec![
+                Value {
+                    value_type: Some(ValueType::BooleanValue(true)),
+                    ..Default::default()
+                },
+                Value {
+                    value_type: Some(ValueType::BlobValue(vec![0xfb, 0xff])),
+                    ..Default::default()
+                },
+            ],
+        });
+        let timestamp = ValueType::TimestampValue(Timestamp {
+            seconds: 1_614_834_367,
+            nanos: 500_000_000,
+        });
+
+        assert_round_trip(&query(Filter::and(vec![
+            property("a", Operator::In, array),
+            property("b", Operator::GreaterThanOrEqual, timestamp),
+            property("c", Operator::Equal, ValueType::DoubleValue(1.0)),
+            property("d", Operator::Equal, ValueType::DoubleValue(1e300)),
+            property(
+                "e",
+                Operator::LessThanOrEqual,
+                ValueType::IntegerValue(i64::MIN),
+            ),
+            property(
+                "f",
+                Operator::Equal,
+                ValueType::StringValue("a\\b\n`".into()),
+            ),
+        ])));
+    }
+
+    #[test]
+    fn round_trip_key_literals() {
+        let keys = [
+            Key::builder().id("Book", 1).build(),
+            Key::builder()
+                .name("Author", "tolkien")
+                .id("Book", 12)
+                .name("Chapter Name", "o'clock")
+                .build(),
+            Key::builder()
+                .namespace("fiction")
+                .name("Author", "tolkien")
+                .id("Book", 12)
+                .build(),
+            Key::builder()
+                .project("library")
+                .namespace("fiction")
+                .id("Book", 12)
+                .build(),
+            Key::builder().project("library").name("KEY", "k").build(),
+        ];
+        for key in keys {
+            assert_round_trip(&query(property(
+                "__key__",
+                Operator::HasAncestor,
+                ValueType::KeyValue(key.clone()),
+            )));
+            assert_round_trip(&query(property(
+                "parent",
+                Operator::Equal,
+                ValueType::KeyValue(key),
+            )));
+        }
+    }
+
+    #[test]
+    fn round_trip_ordering_and_clauses() {
+        let queries = [
+            Query {
+                order: vec![
+                    order("year", Direction::Descending),
+                    order("title", Direction::Ascending),
+                    order("author name", Direction::Descending),
+                ],
+                ..query(property("a", Operator::Equal, ValueType::IntegerValue(1)))
+            },
+            Query {
+                projection: vec![Projection {
+                    property: Some(PropertyReference {
+                        name: "title".to_string(),
+                    }),
+                }],
+                distinct_on: vec![PropertyReference {
+                    name: "author".to_string(),
+                }],
+                order: vec![order("author", Direction::Ascending)],
+                limit: Some(10),
+                offset: 5,
+                ..Default::default()
+            },
+        ];
+        for query in &queries {
+            assert_round_trip(query);
+        }
+
+        for gql in [
+            "SELECT DISTINCT a, b FROM Book ORDER BY a DESC, b LIMIT 10 OFFSET 20",
+            "SELECT DISTINCT ON (a) * FROM `Book Shelf` WHERE `order` = 'x' OFFSET 3",
+        ] {
+            assert_eq!(Query::from_gql(gql).unwrap().to_gql(), gql);
+        }
+    }
+
+    #[test]
+    fn round_trip_cursors() {
+        let query = Query {
+            start_cursor: b"start".to_vec(),
+            end_cursor: b"end".to_vec(),
+            offset: 5,
+            ..Default::default()
+        };
+        let gql = query.to_gql();
+        assert_eq!(gql, "SELECT * LIMIT @end_cursor OFFSET @start_cursor + 5");
+
+        let cursor = |cursor: &[u8]| GqlQueryParameter {
+            parameter_type: Some(ParameterType::Cursor(cursor.to_vec())),
+        };
+        let gql = GqlQuery {
+            query_string: gql,
+            named_bindings: HashMap::from([
+                ("start_cursor".to_string(), cursor(b"start")),
+                ("end_cursor".to_string(), cursor(b"end")),
+            ]),
+            allow_literals: true,
+            ..Default::default()
+        };
+        assert_eq!(Query::from_gql_query(&gql).unwrap(), query);
+    }
+
+    #[test]
+    fn values_without_literals_are_marked() {
+        for (value, marker) in [
+            (ValueType::DoubleValue(f64::INFINITY), "DOUBLE('Infinity')"),
+            (
+                ValueType::DoubleValue(f64::NEG_INFINITY),
+                "DOUBLE('-Infinity')",
+            ),
+            (ValueType::DoubleValue(f64::NAN), "DOUBLE('NaN')"),
+            (
+                ValueType::KeyValue(
+                    Key::builder()
+                        .name("Shelf", "top")
+                        .incomplete("Book")
+                        .build(),
+                ),
+                "KEY(Shelf, 'top', Book, ?)",
+            ),
+        ] {
+            let query = query(property("a", Operator::Equal, value));
+            let gql = query.to_gql();
+            assert_eq!(gql, format!("SELECT * FROM Book WHERE a = {marker}"));
+            assert!(Query::from_gql(&gql).is_err(), "{gql}");
+        }
+
+        let query = Query::from_gql("SELECT * FROM A WHERE a = 1e999").unwrap();
+        assert_eq!(
+            query.to_gql(),
+            "SELECT * FROM A WHERE a = DOUBLE('Infinity')"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::VecDeque, panic, sync::Arc};
+
+use tokio::{sync::Mutex, task::JoinHandle};
+
+use crate::google::datastore::v1::Key;
+use crate::{CloudDatastoreError, Datastore};
+
+///
+/// Hands out keys with numeric ids allocated by Datastore for one kind and parent, without one
+/// request per key.
+///
+/// Ids are allocated in blocks of [`IdAllocator::with_block_size`] keys. When fewer than
+/// [`IdAllocator::with_low_water_mark`] keys are left, the next block is allocated in the
+/// background. Cloned allocators share their keys, so an allocator can be cloned into every task
+/// that needs ids.
+///
+/// ```ignore
+/// let allocator = datastore.id_allocator("Book", None);
+/// let key = allocator.next_key().await?;
+/// ```
+///
+#[derive(Clone)]
+pub struct IdAllocator {
+    datastore: Datastore,
+    kind: String,
+    parent: Option<Key>,
+    block_size: usize,
+    low_water_mark: usize,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    keys: VecDeque<Key>,
+    refill: Option<JoinHandle<Result<Vec<Key>, CloudDatastoreError>>>,
+}
+
+impl IdAllocator {
+    pub(crate) fn new(datastore: Datastore, kind: String, parent: Option<Key>) -> Self {
+        IdAllocator {
+            datastore,
+            kind,
+            parent,
+            block_size: 100,
+            low_water_mark: 20,
+            state: Default::default(),
+        }
+    }
+
+    /// Number of ids allocated per request. Defaults to 100.
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// Number of remaining keys below which the next block is allocated. Defaults to 20.
+    pub fn with_low_water_mark(mut self, low_water_mark: usize) -> Self {
+        self.low_water_mark = low_water_mark;
+        self
+    }
+
+    ///
+    /// The next key with an allocated id. Only waits for Datastore when all allocated keys are
+    /// used up before the next block arrives.
+    ///
+    pub async fn next_key(&self) -> Result<Key, CloudDatastoreError> {
+        let mut state = self.state.lock().await;
+        loop {
+            if state.refill.as_ref().is_some_and(|r| r.is_finished()) {
+                self.finish_refill(&mut state).await?;
+            }
+            let low = state.keys.len() < self.low_water_mark || state.keys.is_empty();
+            if low && state.refill.is_none() {
+                self.start_refill(&mut state);
+            }
+            if let Some(key) = state.keys.pop_front() {
+                return Ok(key);
+            }
+            self.finish_refill(&mut state).await?;
+        }
+    }
+
+    fn start_refill(&self, state: &mut State) {
+        let mut datastore = self.datastore.clone();
+        let kind = self.kind.clone();
+        let parent = self.parent.clone();
+        let n = self.block_size;
+        state.refill = Some(tokio::spawn(async move {
+            datastore.allocate_ids(kind, parent, n).await
+        }));
+    }
+
+    async fn finish_refill(&self, state: &mut State) -> Result<(), CloudDatastoreError> {
+        let Some(refill) = state.refill.take() else {
+            return Ok(());
+        };
+        match refill.await {
+            Ok(keys) => state.keys.extend(keys?),
+            Err(error) if error.is_panic() => panic::resume_unwind(error.into_panic()),
+            // The runtime is shutting down; the next call starts a new refill.
+            Err(_) => {}
+        }
+        Ok(())
+    }
+}
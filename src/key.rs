@@ -0,0 +1,252 @@
+use crate::google::datastore::v1::{
+    key::{path_element::IdType, PathElement},
+    Key, PartitionId,
+};
+
+impl Key {
+    pub fn builder() -> KeyBuilder {
+        KeyBuilder::default()
+    }
+
+    /// The kind of the entity the key refers to, which is the kind of the last path element.
+    pub fn leaf_kind(&self) -> Option<&str> {
+        self.path.last().map(|element| element.kind.as_str())
+    }
+
+    /// The numeric id of the entity the key refers to, if it has one.
+    pub fn leaf_id(&self) -> Option<i64> {
+        match self.path.last()?.id_type {
+            Some(IdType::Id(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The name of the entity the key refers to, if it has one.
+    pub fn leaf_name(&self) -> Option<&str> {
+        match &self.path.last()?.id_type {
+            Some(IdType::Name(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Whether every path element has an id or a name. Incomplete keys get an id allocated when
+    /// the entity is inserted.
+    pub fn is_complete(&self) -> bool {
+        !self.path.is_empty() && self.path.iter().all(|element| element.id_type.is_some())
+    }
+
+    /// The key of the parent entity, or `None` for a root entity.
+    pub fn parent(&self) -> Option<Key> {
+        let (_, parent_path) = self.path.split_last()?;
+        if parent_path.is_empty() {
+            return None;
+        }
+        Some(Key {
+            partition_id: self.partition_id.clone(),
+            path: parent_path.to_vec(),
+        })
+    }
+
+    /// The keys of all ancestors, starting with the parent and ending with the root entity.
+    pub fn ancestors(&self) -> Vec<Key> {
+        (1..self.path.len())
+            .rev()
+            .map(|len| Key {
+                partition_id: self.partition_id.clone(),
+                path: self.path[..len].to_vec(),
+            })
+            .collect()
+    }
+}
+
+///
+/// Builder for creating a key.
+///
+/// Path elements are added from the root ancestor to the entity itself:
+///
+/// ```ignore
+/// let key = Key::builder()
+///     .namespace("library")
+///     .name("Book", "book_one")
+///     .id("Chapter", 12)
+///     .build();
+/// ```
+///
+/// An empty project or database refers to the project and database of the
+/// [`Datastore`](crate::Datastore) instance.
+///
+#[derive(Clone, Debug, Default)]
+pub struct KeyBuilder {
+    key: Key,
+}
+
+impl KeyBuilder {
+    /// Start from the key of `parent`, including its partition.
+    pub fn parent(mut self, parent: Key) -> Self {
+        let path = std::mem::take(&mut self.key.path);
+        self.key = parent;
+        self.key.path.extend(path);
+        self
+    }
+
+    pub fn project<T: Into<String>>(mut self, project_id: T) -> Self {
+        self.partition_id().project_id = project_id.into();
+        self
+    }
+
+    pub fn database<T: Into<String>>(mut self, database_id: T) -> Self {
+        self.partition_id().database_id = database_id.into();
+        self
+    }
+
+    pub fn namespace<T: Into<String>>(mut self, namespace_id: T) -> Self {
+        self.partition_id().namespace_id = namespace_id.into();
+        self
+    }
+
+    /// Add a path element with a name.
+    pub fn name<K: Into<String>, N: Into<String>>(self, kind: K, name: N) -> Self {
+        self.element(kind.into(), Some(IdType::Name(name.into())))
+    }
+
+    /// Add a path element with a numeric id.
+    pub fn id<K: Into<String>>(self, kind: K, id: i64) -> Self {
+        self.element(kind.into(), Some(IdType::Id(id)))
+    }
+
+    /// Add a path element without id or name. Only the last element of a key may be incomplete.
+    pub fn incomplete<K: Into<String>>(self, kind: K) -> Self {
+        self.element(kind.into(), None)
+    }
+
+    pub fn build(self) -> Key {
+        self.key
+    }
+
+    fn element(mut self, kind: String, id_type: Option<IdType>) -> Self {
+        self.key.path.push(PathElement { kind, id_type });
+        self
+    }
+
+    fn partition_id(&mut self) -> &mut PartitionId {
+        self.key.partition_id.get_or_insert_with(Default::default)
+    }
+}
+
+impl From<KeyBuilder> for Key {
+    fn from(builder: KeyBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::EntityBuilder;
+
+    use super::*;
+
+    fn partition() -> PartitionId {
+        PartitionId {
+            project_id: "library".to_string(),
+            database_id: "db".to_string(),
+            namespace_id: "fiction".to_string(),
+        }
+    }
+
+    fn chapter_key() -> Key {
+        Key::builder()
+            .project("library")
+            .database("db")
+            .namespace("fiction")
+            .name("Author", "tolkien")
+            .id("Book", 12)
+            .name("Chapter", "riddles")
+            .build()
+    }
+
+    #[test]
+    fn root_key_has_no_ancestors() {
+        let key = Key::builder().project("library").id("Book", 12).build();
+        assert_eq!(key.parent(), None);
+        assert!(key.ancestors().is_empty());
+
+        assert_eq!(Key::default().parent(), None);
+        assert!(Key::default().ancestors().is_empty());
+    }
+
+    #[test]
+    fn ancestors_start_with_parent() {
+        let key = chapter_key();
+        let book = Key {
+            partition_id: Some(partition()),
+            path: key.path[..2].to_vec(),
+        };
+        let author = Key {
+            partition_id: Some(partition()),
+            path: key.path[..1].to_vec(),
+        };
+
+        assert_eq!(key.parent(), Some(book.clone()));
+        assert_eq!(key.ancestors(), [book.clone(), author.clone()]);
+        assert_eq!(book.parent(), Some(author));
+    }
+
+    #[test]
+    fn leaf_accessors() {
+        let key = chapter_key();
+        assert_eq!(key.leaf_kind(), Some("Chapter"));
+        assert_eq!(key.leaf_name(), Some("riddles"));
+        assert_eq!(key.leaf_id(), None);
+        assert_eq!(key.parent().unwrap().leaf_id(), Some(12));
+        assert!(key.is_complete());
+
+        let key = Key::builder().id("Book", 12).incomplete("Chapter").build();
+        assert_eq!(key.leaf_kind(), Some("Chapter"));
+        assert!(!key.is_complete());
+        assert!(!Key::default().is_complete());
+    }
+
+    #[test]
+    fn builder_parent_after_path_elements() {
+        let parent = Key::builder()
+            .project("library")
+            .database("db")
+            .namespace("fiction")
+            .name("Author", "tolkien")
+            .build();
+        let key = Key::builder()
+            .id("Book", 12)
+            .name("Chapter", "riddles")
+            .parent(parent)
+            .build();
+        assert_eq!(key, chapter_key());
+
+        // The partition comes from the parent.
+        let key = Key::builder()
+            .namespace("other")
+            .id("Book", 12)
+            .parent(Key::builder().name("Author", "tolkien").build())
+            .build();
+        assert_eq!(key.partition_id, None);
+    }
+
+    #[test]
+    fn kind_and_name_read_the_leaf() {
+        let key = chapter_key();
+        assert_eq!(key.kind().unwrap(), "Chapter");
+        assert_eq!(key.name().unwrap(), "riddles");
+
+        let key = Key::builder()
+            .name("Author", "tolkien")
+            .id("Book", 12)
+            .build();
+        assert_eq!(key.kind().unwrap(), "Book");
+        assert!(key.name().is_err());
+        assert!(Key::default().kind().is_err());
+        assert!(Key::default().name().is_err());
+
+        let entity = EntityBuilder::new().with_key(chapter_key()).build();
+        assert!(entity.req_key("Chapter").is_ok());
+        assert!(entity.req_key("Author").is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    fmt::{self, Display, Formatter, Write},
+    str::FromStr,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use prost::Message;
+
+use crate::google::datastore::v1::{
+    key::{path_element::IdType, PathElement},
+    Key, PartitionId,
+};
+use crate::KeyError;
+
+/// The legacy App Engine `Reference` message, as serialized by ndb.
+#[derive(Clone, PartialEq, Message)]
+struct Reference {
+    #[prost(string, required, tag = "13")]
+    app: String,
+    #[prost(message, required, tag = "14")]
+    path: Path,
+    #[prost(string, optional, tag = "20")]
+    name_space: Option<String>,
+    #[prost(string, optional, tag = "23")]
+    database_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Path {
+    #[prost(group, repeated, tag = "1")]
+    element: Vec<Element>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Element {
+    #[prost(string, required, tag = "2")]
+    r#type: String,
+    #[prost(int64, optional, tag = "3")]
+    id: Option<i64>,
+    #[prost(string, optional, tag = "4")]
+    name: Option<String>,
+}
+
+impl Key {
+    ///
+    /// Encode the key in the URL-safe format of App Engine and ndb's `Key.urlsafe()`: an
+    /// unpadded URL-safe base64 encoding of the legacy `Reference` message.
+    ///
+    /// The project, namespace and database are taken from the partition of the key. Keys built
+    /// locally have no project unless it is set, for example with
+    /// [`KeyBuilder::project`](crate::KeyBuilder::project), and keys without a project or path
+    /// are rejected, as [`Key::from_urlsafe`] could not decode them.
+    ///
+    /// ```
+    /// use cloud_datastore_rs::google::datastore::v1::Key;
+    ///
+    /// let key = Key::builder().project("example").id("Kind", 1337).build();
+    /// assert_eq!(key.to_urlsafe().unwrap(), "agdleGFtcGxlcgsLEgRLaW5kGLkKDA");
+    ///
+    /// assert!(Key::builder().id("Kind", 1337).build().to_urlsafe().is_err());
+    /// ```
+    ///
+    pub fn to_urlsafe(&self) -> Result<String, KeyError> {
+        let partition_id = self.partition_id.clone().unwrap_or_default();
+        if partition_id.project_id.is_empty() {
+            return Err(KeyError(format!(
+                "Cannot encode key {self} without a project as urlsafe"
+            )));
+        }
+        if self.path.is_empty() {
+            return Err(KeyError(
+                "Cannot encode key without a path as urlsafe".to_string(),
+            ));
+        }
+
+        let reference = Reference {
+            app: partition_id.project_id,
+            path: Path {
+                element: self
+                    .path
+                    .iter()
+                    .map(|element| Element {
+                        r#type: element.kind.clone(),
+                        id: match element.id_type {
+                            Some(IdType::Id(id)) => Some(id),
+                            _ => None,
+                        },
+                        name: match &element.id_type {
+                            Some(IdType::Name(name)) => Some(name.clone()),
+                            _ => None,
+                        },
+                    })
+                    .collect(),
+            },
+            name_space: Some(partition_id.namespace_id).filter(|ns| !ns.is_empty()),
+            database_id: Some(partition_id.database_id).filter(|db| !db.is_empty()),
+        };
+        Ok(URL_SAFE_NO_PAD.encode(reference.encode_to_vec()))
+    }
+
+    ///
+    /// Decode a key encoded by [`Key::to_urlsafe`], App Engine or ndb. The location prefix of
+    /// legacy App Engine application ids, such as `s~`, is removed from the project. Keys without
+    /// an application id or path elements are rejected.
+    ///
+    /// ```
+    /// use cloud_datastore_rs::google::datastore::v1::Key;
+    ///
+    /// let key = Key::from_urlsafe(
+    ///     "agxzfnNhbXBsZS1hcHByHgsSBlBhcmVudBg7DAsSBUNoaWxkIgdGZWF0aGVyDKIBBXNwYWNl",
+    /// )
+    /// .unwrap();
+    /// let expected = Key::builder()
+    ///     .project("sample-app")
+    ///     .namespace("space")
+    ///     .id("Parent", 59)
+    ///     .name("Child", "Feather")
+    ///     .build();
+    /// assert_eq!(key, expected);
+    ///
+    /// let key = Key::from_urlsafe("agZzfmZpcmVyDwsSBEtpbmQiBVRoaW5nDA").unwrap();
+    /// assert_eq!(key, Key::builder().project("fire").name("Kind", "Thing").build());
+    ///
+    /// let key = Key::from_urlsafe("ahhzfnNhbXBsZS1hcHAtbm8tbG9jYXRpb25yCgsSBFpvcnAYWAw").unwrap();
+    /// assert_eq!(key.to_string(), "Zorp:88");
+    /// ```
+    ///
+    pub fn from_urlsafe(urlsafe: &str) -> Result<Key, KeyError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(urlsafe.trim_end_matches('='))
+            .map_err(|e| KeyError(format!("Invalid urlsafe key: {e}")))?;
+        let reference = Reference::decode(bytes.as_slice())
+            .map_err(|e| KeyError(format!("Invalid urlsafe key: {e}")))?;
+        // Required fields are not enforced when decoding, so an empty input decodes as well.
+        if reference.app.is_empty() || reference.path.element.is_empty() {
+            return Err(KeyError(format!(
+                "Invalid urlsafe key '{urlsafe}': missing app or path"
+            )));
+        }
+
+        let project_id = match reference.app.split_once('~') {
+            Some((_, project_id)) => project_id.to_string(),
+            None => reference.app,
+        };
+        let path = reference
+            .path
+            .element
+            .into_iter()
+            .map(|element| PathElement {
+                kind: element.r#type,
+                id_type: match (element.id, element.name) {
+                    (Some(id), _) => Some(IdType::Id(id)),
+                    (None, Some(name)) => Some(IdType::Name(name)),
+                    (None, None) => None,
+                },
+            })
+            .collect();
+
+        Ok(Key {
+            partition_id: Some(PartitionId {
+                project_id,
+                database_id: reference.database_id.unwrap_or_default(),
+                namespace_id: reference.name_space.unwrap_or_default(),
+            }),
+            path,
+        })
+    }
+}
+
+///
+/// Formats the path of the key, from the root ancestor to the entity, as `Kind:id` or
+/// `Kind:"name"` elements separated by `/`, for example `Book:"book_one"/Chapter:12`. An
+/// incomplete element is written as its kind alone. The partition is not included.
+///
+/// Kinds that are not made of letters, digits, `_`, `-` and `.` are quoted like names. The
+/// format is parsed back by `FromStr`.
+///
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, element) in self.path.iter().enumerate() {
+            if i > 0 {
+                f.write_char('/')?;
+            }
+            if is_plain_kind(&element.kind) {
+                f.write_str(&element.kind)?;
+            } else {
+                write_quoted(f, &element.kind)?;
+            }
+            match &element.id_type {
+                Some(IdType::Id(id)) => write!(f, ":{id}")?,
+                Some(IdType::Name(name)) => {
+                    f.write_char(':')?;
+                    write_quoted(f, name)?;
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Key {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars().peekable();
+        let mut path = Vec::new();
+
+        loop {
+            let kind = match chars.peek() {
+                Some('"') => read_quoted(&mut chars)?,
+                _ => read_while(&mut chars, |c| c != ':' && c != '/'),
+            };
+            if kind.is_empty() {
+                return Err(KeyError(format!("Missing kind in key '{s}'")));
+            }
+
+            let id_type = match chars.peek() {
+                Some(':') => {
+                    chars.next();
+                    match chars.peek() {
+                        Some('"') => Some(IdType::Name(read_quoted(&mut chars)?)),
+                        _ => {
+                            let id = read_while(&mut chars, |c| c != '/');
+                            let id = id
+                                .parse()
+                                .map_err(|_| KeyError(format!("Invalid id '{id}' in key '{s}'")))?;
+                            Some(IdType::Id(id))
+                        }
+                    }
+                }
+                _ => None,
+            };
+            path.push(PathElement { kind, id_type });
+
+            match chars.next() {
+                Some('/') => continue,
+                None => break,
+                Some(c) => return Err(KeyError(format!("Unexpected '{c}' in key '{s}'"))),
+            }
+        }
+
+        Ok(Key {
+            path,
+            ..Default::default()
+        })
+    }
+}
+
+fn is_plain_kind(kind: &str) -> bool {
+    !kind.is_empty()
+        && kind
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+fn write_quoted(f: &mut Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('"')
+}
+
+fn read_quoted(chars: &mut impl Iterator<Item = char>) -> Result<String, KeyError> {
+    chars.next(); // Opening quote.
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some(c) => s.push(c),
+                None => break,
+            },
+            Some(c) => s.push(c),
+            None => break,
+        }
+    }
+    Err(KeyError("Unterminated quoted name in key".to_string()))
+}
+
+fn read_while(
+    chars: &mut std::iter::Peekable<impl Iterator<Item = char>>,
+    predicate: impl Fn(char) -> bool,
+) -> String {
+    let mut s = String::new();
+    while let Some(&c) = chars.peek().filter(|&&c| predicate(c)) {
+        s.push(c);
+        chars.next();
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_urlsafe_rejects_missing_fields() {
+        // Empty, app only, and path only.
+        for urlsafe in ["", "agdleGFtcGxl", "cgsLEgRLaW5kGLkKDA"] {
+            assert!(Key::from_urlsafe(urlsafe).is_err(), "{urlsafe}");
+        }
+        assert!(Key::from_urlsafe("not base64!").is_err());
+        assert!(Key::from_urlsafe("agdleGFtcGxlcgsLEgRLaW5kGLkK").is_err());
+    }
+
+    #[test]
+    fn urlsafe_round_trip() {
+        let key = Key::builder()
+            .project("example")
+            .database("db")
+            .namespace("ns")
+            .name("Book", "book_one")
+            .id("Chapter", 12)
+            .incomplete("Page")
+            .build();
+        assert_eq!(Key::from_urlsafe(&key.to_urlsafe().unwrap()).unwrap(), key);
+    }
+
+    #[test]
+    fn to_urlsafe_matches_ndb() {
+        let key = Key::builder()
+            .project("s~sample-app")
+            .namespace("space")
+            .id("Parent", 59)
+            .name("Child", "Feather")
+            .build();
+        assert_eq!(
+            key.to_urlsafe().unwrap(),
+            "agxzfnNhbXBsZS1hcHByHgsSBlBhcmVudBg7DAsSBUNoaWxkIgdGZWF0aGVyDKIBBXNwYWNl"
+        );
+
+        let key = Key::builder()
+            .project("s~fire")
+            .name("Kind", "Thing")
+            .build();
+        assert_eq!(
+            key.to_urlsafe().unwrap(),
+            "agZzfmZpcmVyDwsSBEtpbmQiBVRoaW5nDA"
+        );
+    }
+
+    #[test]
+    fn to_urlsafe_rejects_missing_fields() {
+        // Keys built locally have no project.
+        let key = Key::builder().id("Book", 5).build();
+        assert!(key.to_urlsafe().is_err());
+        let key = Key::builder().namespace("space").id("Book", 5).build();
+        assert!(key.to_urlsafe().is_err());
+
+        let key = Key::builder().project("example").build();
+        assert!(key.to_urlsafe().is_err());
+    }
+}
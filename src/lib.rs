@@ -1,32 +1,79 @@
+mod aggregation;
 mod auth_interceptor;
+mod batch;
+mod bulk;
+mod concurrency;
 mod error;
+mod explain;
+mod gql;
+mod id_allocator;
+mod key;
+mod key_format;
+mod query;
+mod transaction;
+mod transform;
+mod typed_key;
+mod vector;
 
 use std::{
+    collections::{HashMap, HashSet},
     error::Error,
     fmt::{self, Display, Formatter},
+    hash::{Hash, Hasher},
     sync::Arc,
 };
 
+pub use aggregation::{AggregationQueryBuilder, AggregationValueError, Numeric};
 use auth_interceptor::AuthInterceptor;
+pub use batch::WriteBatch;
+use bulk::chunk_mutations;
+pub use bulk::{BulkWriteOptions, BulkWriteReport, ChunkResult};
+use concurrency::conflict_error;
+pub use concurrency::{EntityMetadata, UpsertOptions, Versioned};
 pub use error::CloudDatastoreError;
+pub use explain::{ExecutionReport, ExplainReport, StatValue};
+use futures::{future::BoxFuture, stream, Stream, StreamExt};
 use gcp_auth::TokenProvider;
+pub use google::datastore::v1::commit_request::Mode as CommitMode;
 use google::datastore::v1::{
-    commit_request::{Mode as CommitMode, TransactionSelector},
+    commit_request::TransactionSelector,
     datastore_client::DatastoreClient,
+    find_nearest::DistanceMeasure,
     key::{path_element::IdType, PathElement},
-    mutation::Operation,
+    mutation::{ConflictDetectionStrategy, ConflictResolutionStrategy, Operation},
+    query_result_batch::MoreResultsType,
+    read_options::ConsistencyType,
+    run_aggregation_query_request::QueryType as AggregationQueryType,
     run_query_request::QueryType,
-    transaction_options::Mode as TransactionMode,
+    transaction_options::{Mode as TransactionMode, ReadOnly, ReadWrite},
     value::ValueType,
-    ArrayValue, CommitRequest, CommitResponse, Entity, Key, KindExpression, Mutation, Query,
+    AggregationQuery, AggregationResult, AllocateIdsRequest, ArrayValue, BeginTransactionRequest,
+    CommitRequest, CommitResponse, Entity, EntityResult, ExplainMetrics, ExplainOptions, Key,
+    LookupRequest, Mutation, MutationResult, PartitionId, PropertyMask, Query, QueryResultBatch,
+    ReadOptions, ReserveIdsRequest, RunAggregationQueryRequest, RunAggregationQueryResponse,
     RunQueryRequest, RunQueryResponse, TransactionOptions, Value,
 };
-
-use tonic::transport::{Channel, ClientTlsConfig};
+pub use gql::{GqlParseError, GqlQueryBuilder};
+pub use id_allocator::IdAllocator;
+pub use key::KeyBuilder;
+use prost::Message;
+use prost_types::Timestamp;
+pub use query::{Cursor, CursorError, QueryBuilder};
+use tonic::{
+    transport::{Channel, ClientTlsConfig},
+    Code, Status,
+};
 use tower::ServiceBuilder;
 use tracing::debug;
+pub use transaction::{RetryPolicy, Transaction};
+pub use transform::{TransformBuilder, TransformResults};
+pub use typed_key::{EntityKey, TypedKey};
+pub use vector::Vector;
+use vector::VECTOR_MEANING;
 
 const HTTP_ENDPOINT: &str = "https://datastore.googleapis.com";
+const DISTANCE_RESULT_PROPERTY: &str = "_vector_distance";
+const MAX_LOOKUP_KEYS: usize = 1000;
 
 pub mod google {
     #[path = ""]
@@ -74,6 +121,68 @@ impl From<String> for ValueType {
     }
 }
 
+impl From<&str> for ValueType {
+    fn from(s: &str) -> Self {
+        ValueType::StringValue(s.to_string())
+    }
+}
+
+impl From<&String> for ValueType {
+    fn from(s: &String) -> Self {
+        ValueType::StringValue(s.clone())
+    }
+}
+
+impl From<bool> for ValueType {
+    fn from(b: bool) -> Self {
+        ValueType::BooleanValue(b)
+    }
+}
+
+impl From<i32> for ValueType {
+    fn from(i: i32) -> Self {
+        ValueType::IntegerValue(i.into())
+    }
+}
+
+impl From<i64> for ValueType {
+    fn from(i: i64) -> Self {
+        ValueType::IntegerValue(i)
+    }
+}
+
+impl From<f64> for ValueType {
+    fn from(d: f64) -> Self {
+        ValueType::DoubleValue(d)
+    }
+}
+
+impl From<Key> for ValueType {
+    fn from(key: Key) -> Self {
+        ValueType::KeyValue(key)
+    }
+}
+
+impl From<Timestamp> for ValueType {
+    fn from(t: Timestamp) -> Self {
+        ValueType::TimestampValue(t)
+    }
+}
+
+impl<V: Into<ValueType>> From<Vec<V>> for ValueType {
+    fn from(values: Vec<V>) -> Self {
+        ValueType::ArrayValue(ArrayValue {
+            values: values
+                .into_iter()
+                .map(|v| Value {
+                    value_type: Some(v.into()),
+                    ..Default::default()
+                })
+                .collect(),
+        })
+    }
+}
+
 #[cfg(feature = "time")]
 impl From<time::OffsetDateTime> for ValueType {
     fn from(t: time::OffsetDateTime) -> Self {
@@ -88,6 +197,12 @@ pub trait TryFromEntity: Sized {
     fn try_from_entity(entity: Entity) -> Result<Self, TryFromEntityError>;
 }
 
+impl TryFromEntity for Entity {
+    fn try_from_entity(entity: Entity) -> Result<Self, TryFromEntityError> {
+        Ok(entity)
+    }
+}
+
 pub trait Kind {
     fn kind() -> &'static str;
 }
@@ -144,27 +259,12 @@ impl Datastore {
         &mut self,
         entities: Vec<impl Into<Entity>>,
     ) -> Result<CommitResponse, CloudDatastoreError> {
-        let mutations: Vec<Mutation> = entities
+        let mutations = entities
             .into_iter()
-            .map(|e| Mutation {
-                operation: Some(Operation::Upsert(e.into())),
-                ..Default::default()
-            })
+            .map(|e| mutation(Operation::Upsert(e.into())))
             .collect();
-
-        let request = CommitRequest {
-            project_id: self.project_id.clone(),
-            database_id: self.database_id.clone(), // use empty string '' to refer the default database.
-            mode: CommitMode::Transactional as i32,
-            transaction_selector: Some(TransactionSelector::SingleUseTransaction(
-                TransactionOptions {
-                    mode: Some(TransactionMode::ReadWrite(Default::default())),
-                },
-            )),
-            mutations,
-        };
-
-        Ok(self.service.commit(request).await?.into_inner())
+        self.commit_mutations(mutations, CommitMode::Transactional)
+            .await
     }
 
     ///
@@ -174,38 +274,169 @@ impl Datastore {
         &mut self,
         entity: impl Into<Entity>,
     ) -> Result<CommitResponse, CloudDatastoreError> {
-        let entity = entity.into();
+        let mutations = vec![mutation(Operation::Upsert(entity.into()))];
+        self.commit_mutations(mutations, CommitMode::NonTransactional)
+            .await
+    }
 
-        let request = CommitRequest {
-            project_id: self.project_id.clone(),
-            database_id: self.database_id.clone(), // use empty string '' to refer the default database.
-            mode: CommitMode::NonTransactional as i32,
-            mutations: vec![Mutation {
-                operation: Some(Operation::Upsert(entity)),
-                ..Default::default()
-            }],
+    ///
+    /// Upsert an entity with conflict handling and property transforms, see [`UpsertOptions`].
+    ///
+    /// A conflict resolved with [`ConflictResolutionStrategy::ServerValue`] is not an error: the
+    /// returned mutation result has `conflict_detected` set instead.
+    ///
+    pub async fn upsert_entity_with_options(
+        &mut self,
+        entity: impl Into<Entity>,
+        options: UpsertOptions,
+    ) -> Result<CommitResponse, CloudDatastoreError> {
+        let conflict_resolution_strategy = match options.conflict_detection {
+            Some(_) => options.conflict_resolution as i32,
+            None => ConflictResolutionStrategy::StrategyUnspecified as i32,
+        };
+        let mutations = vec![Mutation {
+            operation: Some(Operation::Upsert(entity.into())),
+            conflict_detection_strategy: options.conflict_detection,
+            conflict_resolution_strategy,
+            property_transforms: options.transforms,
             ..Default::default()
+        }];
+        self.commit_mutations(mutations, CommitMode::NonTransactional)
+            .await
+    }
+
+    ///
+    /// Insert an entity. Fails with [`CloudDatastoreError::AlreadyExists`] if an entity with the
+    /// same key already exists.
+    ///
+    pub async fn insert_entity(
+        &mut self,
+        entity: impl Into<Entity>,
+    ) -> Result<CommitResponse, CloudDatastoreError> {
+        let mutations = vec![mutation(Operation::Insert(entity.into()))];
+        self.commit_mutations(mutations, CommitMode::NonTransactional)
+            .await
+            .map_err(CloudDatastoreError::classify_mutation_error)
+    }
+
+    ///
+    /// Insert entities atomically. Fails with [`CloudDatastoreError::AlreadyExists`], without
+    /// inserting any entity, if an entity with the same key as any of them already exists.
+    ///
+    pub async fn insert_entities(
+        &mut self,
+        entities: Vec<impl Into<Entity>>,
+    ) -> Result<CommitResponse, CloudDatastoreError> {
+        let mutations = entities
+            .into_iter()
+            .map(|e| mutation(Operation::Insert(e.into())))
+            .collect();
+        self.commit_mutations(mutations, CommitMode::Transactional)
+            .await
+            .map_err(CloudDatastoreError::classify_mutation_error)
+    }
+
+    ///
+    /// Insert entities atomically, returning their keys in the order of `entities`. Entities
+    /// with an incomplete key, one whose last path element has no id or name, get a numeric id
+    /// allocated by Datastore, and the returned key is the completed one.
+    ///
+    pub async fn insert_entities_returning_keys(
+        &mut self,
+        entities: Vec<impl Into<Entity>>,
+    ) -> Result<Vec<Key>, CloudDatastoreError> {
+        let entities: Vec<Entity> = entities.into_iter().map(Into::into).collect();
+        let keys: Vec<Option<Key>> = entities.iter().map(|e| e.key.clone()).collect();
+        let response = self.insert_entities(entities).await?;
+        Ok(keys
+            .into_iter()
+            .zip(response.mutation_results)
+            .map(|(key, result)| result.key.or(key).unwrap_or_default())
+            .collect())
+    }
+
+    ///
+    /// Allocate `n` numeric ids for entities of `kind`, optionally under `parent`. The returned
+    /// keys are complete and are never assigned by Datastore to another entity.
+    ///
+    pub async fn allocate_ids(
+        &mut self,
+        kind: impl Into<String>,
+        parent: Option<Key>,
+        n: usize,
+    ) -> Result<Vec<Key>, CloudDatastoreError> {
+        let key = incomplete_key(kind.into(), parent);
+        let request = AllocateIdsRequest {
+            project_id: self.project_id.clone(),
+            database_id: self.database_id.clone(),
+            keys: vec![key; n],
         };
+        Ok(self.service.allocate_ids(request).await?.into_inner().keys)
+    }
 
-        Ok(self.service.commit(request).await?.into_inner())
+    ///
+    /// Create an [`IdAllocator`] that hands out keys of `kind` under `parent`, with ids allocated
+    /// in blocks in the background.
+    ///
+    pub fn id_allocator(&self, kind: impl Into<String>, parent: Option<Key>) -> IdAllocator {
+        IdAllocator::new(self.clone(), kind.into(), parent)
     }
 
     ///
-    /// Delete an entity.
+    /// Prevent the numeric ids of `keys`, which were chosen outside of Datastore, from being
+    /// allocated automatically.
     ///
-    pub async fn delete_entity(&mut self, key: impl Into<Key>) -> Result<(), CloudDatastoreError> {
-        let key = key.into();
-        let request = CommitRequest {
+    pub async fn reserve_ids(
+        &mut self,
+        keys: Vec<impl Into<Key>>,
+    ) -> Result<(), CloudDatastoreError> {
+        let request = ReserveIdsRequest {
             project_id: self.project_id.clone(),
-            database_id: self.database_id.clone(), // use empty string '' to refer the default database.
-            mode: CommitMode::NonTransactional as i32,
-            mutations: vec![Mutation {
-                operation: Some(Operation::Delete(key)),
-                ..Default::default()
-            }],
-            ..Default::default()
+            database_id: self.database_id.clone(),
+            keys: keys.into_iter().map(Into::into).collect(),
         };
-        self.service.commit(request).await?;
+        self.service.reserve_ids(request).await?;
+        Ok(())
+    }
+
+    ///
+    /// Update an entity. Fails with [`CloudDatastoreError::NotFound`] if no entity with the same
+    /// key exists.
+    ///
+    pub async fn update_entity(
+        &mut self,
+        entity: impl Into<Entity>,
+    ) -> Result<CommitResponse, CloudDatastoreError> {
+        let mutations = vec![mutation(Operation::Update(entity.into()))];
+        self.commit_mutations(mutations, CommitMode::NonTransactional)
+            .await
+            .map_err(CloudDatastoreError::classify_mutation_error)
+    }
+
+    ///
+    /// Update entities atomically. Fails with [`CloudDatastoreError::NotFound`], without updating
+    /// any entity, if no entity with the same key as any of them exists.
+    ///
+    pub async fn update_entities(
+        &mut self,
+        entities: Vec<impl Into<Entity>>,
+    ) -> Result<CommitResponse, CloudDatastoreError> {
+        let mutations = entities
+            .into_iter()
+            .map(|e| mutation(Operation::Update(e.into())))
+            .collect();
+        self.commit_mutations(mutations, CommitMode::Transactional)
+            .await
+            .map_err(CloudDatastoreError::classify_mutation_error)
+    }
+
+    ///
+    /// Delete an entity.
+    ///
+    pub async fn delete_entity(&mut self, key: impl Into<Key>) -> Result<(), CloudDatastoreError> {
+        let mutations = vec![mutation(Operation::Delete(key.into()))];
+        self.commit_mutations(mutations, CommitMode::NonTransactional)
+            .await?;
         Ok(())
     }
 
@@ -216,28 +447,223 @@ impl Datastore {
         &mut self,
         keys: Vec<impl Into<Key>>,
     ) -> Result<(), CloudDatastoreError> {
-        let mutations: Vec<Mutation> = keys
+        let mutations = keys
             .into_iter()
-            .map(|k| Mutation {
-                operation: Some(Operation::Delete(k.into())),
-                ..Default::default()
-            })
+            .map(|k| mutation(Operation::Delete(k.into())))
             .collect();
+        self.commit_mutations(mutations, CommitMode::Transactional)
+            .await?;
+        Ok(())
+    }
 
-        let request = CommitRequest {
-            project_id: self.project_id.clone(),
-            database_id: self.database_id.clone(), // use empty string '' to refer the default database.
-            mode: CommitMode::Transactional as i32,
-            transaction_selector: Some(TransactionSelector::SingleUseTransaction(
+    ///
+    /// Upsert an entity only if it has not changed since `expected` was read, typically the
+    /// [`EntityMetadata`] returned by [`Datastore::lookup_entity_versioned`]. Fails with
+    /// [`CloudDatastoreError::Conflict`] otherwise.
+    ///
+    pub async fn upsert_entity_checked(
+        &mut self,
+        entity: impl Into<Entity>,
+        expected: impl Into<ConflictDetectionStrategy>,
+    ) -> Result<CommitResponse, CloudDatastoreError> {
+        let entity = entity.into();
+        let key = entity.key.clone().unwrap_or_default();
+        let mutations = vec![checked_mutation(Operation::Upsert(entity), expected.into())];
+        let response = self
+            .commit_mutations(mutations, CommitMode::NonTransactional)
+            .await?;
+        match conflict_error(&response, &[key]) {
+            Some(error) => Err(error),
+            None => Ok(response),
+        }
+    }
+
+    ///
+    /// Update an entity only if it has not changed since `expected` was read. Fails with
+    /// [`CloudDatastoreError::Conflict`] if it has, or with [`CloudDatastoreError::NotFound`] if
+    /// it no longer exists.
+    ///
+    pub async fn update_entity_checked(
+        &mut self,
+        entity: impl Into<Entity>,
+        expected: impl Into<ConflictDetectionStrategy>,
+    ) -> Result<CommitResponse, CloudDatastoreError> {
+        let entity = entity.into();
+        let key = entity.key.clone().unwrap_or_default();
+        let mutations = vec![checked_mutation(Operation::Update(entity), expected.into())];
+        let response = self
+            .commit_mutations(mutations, CommitMode::NonTransactional)
+            .await
+            .map_err(CloudDatastoreError::classify_mutation_error)?;
+        match conflict_error(&response, &[key]) {
+            Some(error) => Err(error),
+            None => Ok(response),
+        }
+    }
+
+    ///
+    /// Delete an entity only if it has not changed since `expected` was read. Fails with
+    /// [`CloudDatastoreError::Conflict`] otherwise.
+    ///
+    pub async fn delete_entity_checked(
+        &mut self,
+        key: impl Into<Key>,
+        expected: impl Into<ConflictDetectionStrategy>,
+    ) -> Result<(), CloudDatastoreError> {
+        let key = key.into();
+        let mutations = vec![checked_mutation(
+            Operation::Delete(key.clone()),
+            expected.into(),
+        )];
+        let response = self
+            .commit_mutations(mutations, CommitMode::NonTransactional)
+            .await?;
+        match conflict_error(&response, &[key]) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    ///
+    /// Update only the `fields` of an existing entity, leaving its other properties untouched.
+    /// Nested properties are selected with dotted paths, such as `meta.tags`. Fields that are
+    /// absent from `entity` are deleted. Fails with [`CloudDatastoreError::NotFound`] if no entity
+    /// with `key` exists.
+    ///
+    pub async fn update_fields(
+        &mut self,
+        key: impl Into<Key>,
+        entity: impl Into<Entity>,
+        fields: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<CommitResponse, CloudDatastoreError> {
+        let entity = Entity {
+            key: Some(key.into()),
+            ..entity.into()
+        };
+        let mutations = vec![Mutation {
+            operation: Some(Operation::Update(entity)),
+            property_mask: Some(property_mask(fields)),
+            ..Default::default()
+        }];
+        self.commit_mutations(mutations, CommitMode::NonTransactional)
+            .await
+            .map_err(CloudDatastoreError::classify_mutation_error)
+    }
+
+    ///
+    /// Commit the mutations of a [`WriteBatch`]. With [`CommitMode::Transactional`] the mutations
+    /// are applied atomically, otherwise each mutation is applied independently.
+    ///
+    /// The returned results are aligned with the order in which the mutations were added to the
+    /// batch, and hold the keys allocated for incomplete keys, the new versions and whether a
+    /// conflict was detected.
+    ///
+    pub async fn commit_batch(
+        &mut self,
+        batch: WriteBatch,
+        mode: CommitMode,
+    ) -> Result<Vec<MutationResult>, CloudDatastoreError> {
+        let response = self
+            .commit_mutations(batch.into_mutations(), mode)
+            .await
+            .map_err(CloudDatastoreError::classify_mutation_error)?;
+        Ok(response.mutation_results)
+    }
+
+    ///
+    /// Commit any number of mutations, split into chunks that respect the limits in `options`.
+    ///
+    /// Chunks are committed concurrently, up to `options.max_concurrency` at a time, and a failed
+    /// chunk does not stop the others. The returned report tells which chunks, and therefore
+    /// which mutations, were committed.
+    ///
+    pub async fn bulk_write(
+        &self,
+        batch: WriteBatch,
+        options: &BulkWriteOptions,
+    ) -> BulkWriteReport {
+        // The size of the request that `commit_mutations` sends for a chunk, without mutations.
+        let base_len = self.commit_request(vec![], options.mode).encoded_len();
+        let max_bytes = options.max_request_bytes.saturating_sub(base_len);
+        let chunks = chunk_mutations(batch.into_mutations(), options.max_mutations, max_bytes);
+
+        let mode = options.mode;
+        let mut chunks: Vec<(usize, ChunkResult)> = stream::iter(chunks.into_iter().enumerate())
+            .map(|(i, (range, mutations))| {
+                let mut datastore = self.clone();
+                async move {
+                    let result = datastore
+                        .commit_mutations(mutations, mode)
+                        .await
+                        .map(|response| response.mutation_results)
+                        .map_err(CloudDatastoreError::classify_mutation_error);
+                    (i, ChunkResult { range, result })
+                }
+            })
+            .buffer_unordered(options.max_concurrency.max(1))
+            .collect()
+            .await;
+
+        chunks.sort_by_key(|(i, _)| *i);
+        BulkWriteReport {
+            chunks: chunks.into_iter().map(|(_, chunk)| chunk).collect(),
+        }
+    }
+
+    /// Upsert any number of entities with [`Datastore::bulk_write`].
+    pub async fn bulk_upsert_entities(
+        &self,
+        entities: Vec<impl Into<Entity>>,
+        options: &BulkWriteOptions,
+    ) -> BulkWriteReport {
+        let mut batch = WriteBatch::new();
+        for entity in entities {
+            batch.upsert_entity(entity);
+        }
+        self.bulk_write(batch, options).await
+    }
+
+    /// Delete any number of entities with [`Datastore::bulk_write`].
+    pub async fn bulk_delete_entities(
+        &self,
+        keys: Vec<impl Into<Key>>,
+        options: &BulkWriteOptions,
+    ) -> BulkWriteReport {
+        let mut batch = WriteBatch::new();
+        for key in keys {
+            batch.delete_entity(key);
+        }
+        self.bulk_write(batch, options).await
+    }
+
+    /// Commit `mutations` outside of an explicit transaction. Transactional commits use a
+    /// single-use read-write transaction, so that the mutations are applied atomically.
+    pub(crate) async fn commit_mutations(
+        &mut self,
+        mutations: Vec<Mutation>,
+        mode: CommitMode,
+    ) -> Result<CommitResponse, CloudDatastoreError> {
+        let request = self.commit_request(mutations, mode);
+        Ok(self.service.commit(request).await?.into_inner())
+    }
+
+    fn commit_request(&self, mutations: Vec<Mutation>, mode: CommitMode) -> CommitRequest {
+        let transaction_selector = match mode {
+            CommitMode::Transactional => Some(TransactionSelector::SingleUseTransaction(
                 TransactionOptions {
                     mode: Some(TransactionMode::ReadWrite(Default::default())),
                 },
             )),
-            mutations,
+            _ => None,
         };
 
-        self.service.commit(request).await?;
-        Ok(())
+        CommitRequest {
+            project_id: self.project_id.clone(),
+            database_id: self.database_id.clone(), // use empty string '' to refer the default database.
+            mode: mode as i32,
+            transaction_selector,
+            mutations,
+        }
     }
 
     ///
@@ -245,61 +671,490 @@ impl Datastore {
     ///
     pub async fn lookup_entity<T: TryFromEntity>(
         &mut self,
-        key: impl Into<Key>,
+        key: impl EntityKey<T>,
     ) -> Result<Option<T>, CloudDatastoreError> {
-        let key = key.into();
+        self.lookup_entity_with_options(key.into_key(), None).await
+    }
 
-        let request = google::datastore::v1::LookupRequest {
-            project_id: self.project_id.clone(),
-            database_id: self.database_id.clone(),
-            keys: vec![key],
-            ..Default::default()
-        };
+    /// Load all entities of a given kind.
+    pub async fn load_entities<T: TryFromEntity + Kind>(
+        &mut self,
+    ) -> Result<Vec<T>, CloudDatastoreError> {
+        self.load_entities_with_options(None).await
+    }
 
-        let response = self.service.lookup(request).await?.into_inner();
+    ///
+    /// Load an entity as it was at `read_time`.
+    ///
+    /// `read_time` must be a microsecond precision timestamp within the past hour or, if
+    /// Point-in-Time Recovery is enabled, a whole minute timestamp within the past 7 days.
+    ///
+    pub async fn lookup_entity_at<T: TryFromEntity>(
+        &mut self,
+        key: impl EntityKey<T>,
+        read_time: Timestamp,
+    ) -> Result<Option<T>, CloudDatastoreError> {
+        self.lookup_entity_with_options(key.into_key(), read_time_options(read_time))
+            .await
+    }
 
-        let Some(result) = response.found.into_iter().next() else {
-            return Ok(None);
-        };
+    /// Load all entities of a given kind as they were at `read_time`.
+    pub async fn load_entities_at<T: TryFromEntity + Kind>(
+        &mut self,
+        read_time: Timestamp,
+    ) -> Result<Vec<T>, CloudDatastoreError> {
+        self.load_entities_with_options(read_time_options(read_time))
+            .await
+    }
 
-        let result = result.entity.map(|e| T::try_from_entity(e)).transpose();
+    ///
+    /// Load entities by key.
+    ///
+    /// The returned vector is aligned with `keys`: each element is the entity for the key at the
+    /// same position, or `None` if no entity exists for it. Keys are sent in batches of up to
+    /// 1000, and keys deferred by Datastore are requested again until they are resolved.
+    ///
+    pub async fn lookup_entities<T: TryFromEntity>(
+        &mut self,
+        keys: Vec<impl EntityKey<T>>,
+    ) -> Result<Vec<Option<T>>, CloudDatastoreError> {
+        let keys = keys.into_iter().map(EntityKey::into_key).collect();
+        self.lookup_entities_with_options(keys, None).await
+    }
 
-        match result {
-            Ok(Some(entity)) => Ok(Some(entity)),
-            Ok(None) => Ok(None),
-            Err(e) => Err(e.into()),
+    ///
+    /// Load entities by key, returning the entities that were found keyed by their key. Keys with
+    /// no entity are absent from the map.
+    ///
+    pub async fn lookup_entities_map<T: TryFromEntity>(
+        &mut self,
+        keys: Vec<impl EntityKey<T>>,
+    ) -> Result<HashMap<Key, T>, CloudDatastoreError> {
+        let keys: Vec<Key> = keys.into_iter().map(EntityKey::into_key).collect();
+        let entities = self
+            .lookup_entities_with_options(keys.clone(), None)
+            .await?;
+        Ok(keys
+            .into_iter()
+            .zip(entities)
+            .filter_map(|(key, entity)| entity.map(|entity| (key, entity)))
+            .collect())
+    }
+
+    ///
+    /// Load an entity together with its [`EntityMetadata`], which can be passed to the checked
+    /// mutations to detect concurrent changes.
+    ///
+    pub async fn lookup_entity_versioned<T: TryFromEntity>(
+        &mut self,
+        key: impl EntityKey<T>,
+    ) -> Result<Option<Versioned<T>>, CloudDatastoreError> {
+        let entities = self.lookup_entities_versioned(vec![key]).await?;
+        Ok(entities.into_iter().next().flatten())
+    }
+
+    ///
+    /// Load entities by key together with their [`EntityMetadata`]. The returned vector is
+    /// aligned with `keys`, as in [`Datastore::lookup_entities`].
+    ///
+    pub async fn lookup_entities_versioned<T: TryFromEntity>(
+        &mut self,
+        keys: Vec<impl EntityKey<T>>,
+    ) -> Result<Vec<Option<Versioned<T>>>, CloudDatastoreError> {
+        let keys = keys.into_iter().map(EntityKey::into_key).collect();
+        let results = self.lookup_entity_results(keys, None, None).await?;
+        let mut entities = Vec::with_capacity(results.len());
+        for result in results {
+            let versioned = match result {
+                Some(EntityResult {
+                    entity: Some(entity),
+                    version,
+                    create_time,
+                    update_time,
+                    ..
+                }) => Some(Versioned {
+                    entity: T::try_from_entity(entity)?,
+                    metadata: EntityMetadata {
+                        version,
+                        create_time,
+                        update_time,
+                    },
+                }),
+                _ => None,
+            };
+            entities.push(versioned);
         }
+        Ok(entities)
     }
 
-    /// Load all entities of a given kind.
-    pub async fn load_entities<T: TryFromEntity + Kind>(
+    ///
+    /// Load only the `fields` of an entity. Nested properties are selected with dotted paths,
+    /// such as `meta.tags`.
+    ///
+    /// The other properties are absent from the entity, so `T` must tolerate missing properties,
+    /// for example by reading them with the `opt_` accessors of [`Entity`]. `Entity` itself can
+    /// be used to get the properties as returned.
+    ///
+    pub async fn lookup_projection<T: TryFromEntity>(
+        &mut self,
+        key: impl EntityKey<T>,
+        fields: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Option<T>, CloudDatastoreError> {
+        let results = self
+            .lookup_entity_results(vec![key.into_key()], None, Some(property_mask(fields)))
+            .await?;
+        let entity = results.into_iter().next().flatten().and_then(|r| r.entity);
+        Ok(entity.map(T::try_from_entity).transpose()?)
+    }
+
+    pub(crate) async fn lookup_entity_with_options<T: TryFromEntity>(
+        &mut self,
+        key: Key,
+        read_options: Option<ReadOptions>,
+    ) -> Result<Option<T>, CloudDatastoreError> {
+        let entities = self
+            .lookup_entities_with_options(vec![key], read_options)
+            .await?;
+        Ok(entities.into_iter().next().flatten())
+    }
+
+    pub(crate) async fn lookup_entities_with_options<T: TryFromEntity>(
+        &mut self,
+        keys: Vec<Key>,
+        read_options: Option<ReadOptions>,
+    ) -> Result<Vec<Option<T>>, CloudDatastoreError> {
+        let results = self.lookup_entity_results(keys, read_options, None).await?;
+        let mut entities = Vec::with_capacity(results.len());
+        for result in results {
+            let entity = result.and_then(|result| result.entity);
+            entities.push(entity.map(T::try_from_entity).transpose()?);
+        }
+        Ok(entities)
+    }
+
+    /// Look up `keys`, returning the results aligned with the keys. Keys are sent in batches of
+    /// up to `MAX_LOOKUP_KEYS`, and deferred keys are requested again, with a backoff, until they
+    /// are resolved. Fails if Datastore keeps deferring every remaining key.
+    /// With a `property_mask`, only the properties in the mask are returned.
+    pub(crate) async fn lookup_entity_results(
+        &mut self,
+        keys: Vec<Key>,
+        read_options: Option<ReadOptions>,
+        property_mask: Option<PropertyMask>,
+    ) -> Result<Vec<Option<EntityResult>>, CloudDatastoreError> {
+        let mut positions: HashMap<Key, Vec<usize>> = HashMap::new();
+        for (i, key) in keys.iter().enumerate() {
+            positions.entry(lookup_identity(key)).or_default().push(i);
+        }
+
+        let mut results = vec![None; keys.len()];
+        let mut requested = HashSet::new();
+        let mut pending: Vec<Key> = keys
+            .into_iter()
+            .filter(|key| requested.insert(lookup_identity(key)))
+            .collect();
+
+        // Deferred keys are requested again after a backoff. Consecutive rounds in which every key
+        // is deferred again are limited to the attempts of the default retry policy.
+        let policy = RetryPolicy::default();
+        let mut deferred = Vec::new();
+        let mut round_len = pending.len();
+        let mut stalled_rounds = 0;
+
+        loop {
+            if pending.is_empty() {
+                if deferred.is_empty() {
+                    break;
+                }
+                if deferred.len() < round_len {
+                    stalled_rounds = 0;
+                } else {
+                    stalled_rounds += 1;
+                    if stalled_rounds >= policy.max_attempts {
+                        return Err(CloudDatastoreError::GrcpError(Status::unavailable(
+                            format!(
+                                "Lookup of {} keys still deferred after {stalled_rounds} attempts",
+                                deferred.len()
+                            ),
+                        )));
+                    }
+                }
+                let backoff = policy.backoff(stalled_rounds + 1);
+                debug!(
+                    deferred = deferred.len(),
+                    ?backoff,
+                    "Lookup deferred keys, requesting them again."
+                );
+                tokio::time::sleep(backoff).await;
+                pending = std::mem::take(&mut deferred);
+                round_len = pending.len();
+            }
+
+            let split = pending.len().saturating_sub(MAX_LOOKUP_KEYS);
+            let batch = pending.split_off(split);
+
+            let request = LookupRequest {
+                project_id: self.project_id.clone(),
+                database_id: self.database_id.clone(),
+                read_options: read_options.clone(),
+                keys: batch,
+                property_mask: property_mask.clone(),
+            };
+
+            let response = self.service.lookup(request).await?.into_inner();
+            for found in response.found {
+                let Some(key) = found.entity.as_ref().and_then(|e| e.key.as_ref()) else {
+                    continue;
+                };
+                let Some(indexes) = positions.get(&lookup_identity(key)) else {
+                    continue;
+                };
+                for &i in indexes {
+                    results[i] = Some(found.clone());
+                }
+            }
+
+            deferred.extend(response.deferred);
+        }
+
+        Ok(results)
+    }
+
+    pub(crate) async fn load_entities_with_options<T: TryFromEntity + Kind>(
+        &mut self,
+        read_options: Option<ReadOptions>,
+    ) -> Result<Vec<T>, CloudDatastoreError> {
+        self.query_entities_with_options(Query::kind::<T>().build(), read_options)
+            .await
+    }
+
+    ///
+    /// Load the entities matching a query, usually created with a [`QueryBuilder`].
+    ///
+    pub async fn query_entities<T: TryFromEntity>(
         &mut self,
+        query: impl Into<Query>,
     ) -> Result<Vec<T>, CloudDatastoreError> {
+        self.query_entities_with_options(query.into(), None).await
+    }
+
+    ///
+    /// Load a single page of the entities matching a query.
+    ///
+    /// The size of the page is given by the limit of the query. Alongside the entities, a
+    /// [`Cursor`] is returned if there may be more results after this page. To load the next page,
+    /// run the same query again with its start cursor set to the returned cursor.
+    ///
+    pub async fn query_page<T: TryFromEntity>(
+        &mut self,
+        query: impl Into<Query>,
+    ) -> Result<(Vec<T>, Option<Cursor>), CloudDatastoreError> {
+        let query = QueryType::Query(query.into());
+        let (entity_results, cursor) = self.fetch_entity_results(query, None).await?;
+        Ok((decode_entity_results(entity_results)?, cursor))
+    }
+
+    pub(crate) async fn query_entities_with_options<T: TryFromEntity>(
+        &mut self,
+        query: Query,
+        read_options: Option<ReadOptions>,
+    ) -> Result<Vec<T>, CloudDatastoreError> {
+        let query = QueryType::Query(query);
+        let (entity_results, _) = self.fetch_entity_results(query, read_options).await?;
+        Ok(decode_entity_results(entity_results)?)
+    }
+
+    ///
+    /// Find the `limit` entities of kind `T` whose vector property `property` is nearest to
+    /// `query_vector`, returned with their distance from it, nearest first.
+    ///
+    /// To pre-filter the candidates or set a distance threshold, build the query with
+    /// [`QueryBuilder::find_nearest`] and run it with [`Datastore::query_nearest`].
+    ///
+    pub async fn find_nearest<T: TryFromEntity + Kind>(
+        &mut self,
+        property: &str,
+        query_vector: &[f64],
+        measure: DistanceMeasure,
+        limit: i32,
+    ) -> Result<Vec<(T, f64)>, CloudDatastoreError> {
+        let query = Query::kind::<T>().find_nearest(property, query_vector, measure, limit);
+        self.query_nearest(query).await
+    }
+
+    ///
+    /// Run a nearest neighbour query, built with [`QueryBuilder::find_nearest`], returning each
+    /// entity with its distance from the query vector.
+    ///
+    /// If the query does not set a distance result property, one is added. The distance result
+    /// property is removed from the entity before it is converted.
+    ///
+    pub async fn query_nearest<T: TryFromEntity>(
+        &mut self,
+        query: impl Into<Query>,
+    ) -> Result<Vec<(T, f64)>, CloudDatastoreError> {
+        let mut query = query.into();
+        let mut distance_property = DISTANCE_RESULT_PROPERTY.to_string();
+        if let Some(find_nearest) = query.find_nearest.as_mut() {
+            if find_nearest.distance_result_property.is_empty() {
+                find_nearest.distance_result_property = distance_property.clone();
+            } else {
+                distance_property = find_nearest.distance_result_property.clone();
+            }
+        }
+
+        let (entity_results, _) = self
+            .fetch_entity_results(QueryType::Query(query), None)
+            .await?;
+
+        let mut results = Vec::with_capacity(entity_results.len());
+        for mut entity in entity_results.into_iter().filter_map(|found| found.entity) {
+            let distance = match entity.properties.remove(&distance_property) {
+                Some(Value {
+                    value_type: Some(ValueType::DoubleValue(distance)),
+                    ..
+                }) => distance,
+                _ => {
+                    return Err(TryFromEntityError::from(EntityValueError(format!(
+                        "Missing distance result property {distance_property}"
+                    )))
+                    .into())
+                }
+            };
+            results.push((T::try_from_entity(entity)?, distance));
+        }
+        Ok(results)
+    }
+
+    ///
+    /// Stream the entities matching a query.
+    ///
+    /// Batches are fetched lazily: the next batch is only requested from the end cursor of the
+    /// previous one once the consumer has polled past its last entity, keeping memory use bounded
+    /// regardless of the number of results.
+    ///
+    pub fn stream_query<T: TryFromEntity>(
+        &self,
+        query: impl Into<Query>,
+    ) -> impl Stream<Item = Result<T, CloudDatastoreError>> {
+        let state = Some((self.clone(), QueryType::Query(query.into())));
+        stream::unfold(state, |state| async move {
+            let (mut datastore, mut query) = state?;
+            match datastore.fetch_next_batch(&mut query, None).await {
+                Ok((entity_results, NextBatch::More)) => {
+                    Some((Ok(entity_results), Some((datastore, query))))
+                }
+                Ok((entity_results, NextBatch::Done(_))) => Some((Ok(entity_results), None)),
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+        .flat_map(|batch| {
+            let mut results = vec![];
+            match batch {
+                Ok(entity_results) => {
+                    for entity in entity_results.into_iter().filter_map(|found| found.entity) {
+                        results.push(T::try_from_entity(entity).map_err(Into::into));
+                    }
+                }
+                Err(e) => results.push(Err(e)),
+            }
+            stream::iter(results)
+        })
+    }
+
+    /// Run a query to completion, issuing follow-up requests from the end cursor of each batch
+    /// until there are no more results or the limit of the query is reached. Returns the results
+    /// and, unless the query is exhausted, the cursor after the last result.
+    pub(crate) async fn fetch_entity_results(
+        &mut self,
+        mut query: QueryType,
+        read_options: Option<ReadOptions>,
+    ) -> Result<(Vec<EntityResult>, Option<Cursor>), CloudDatastoreError> {
+        let mut entity_results = vec![];
+
+        loop {
+            let (batch, next) = self
+                .fetch_next_batch(&mut query, read_options.clone())
+                .await?;
+            entity_results.extend(batch);
+
+            if let NextBatch::Done(cursor) = next {
+                return Ok((entity_results, cursor));
+            }
+        }
+    }
+
+    /// Fetch a single batch of results, advancing `query` so that running it again fetches the
+    /// following batch. GQL queries are replaced by their parsed form, as returned by Datastore,
+    /// so that following batches can be fetched from a cursor.
+    async fn fetch_next_batch(
+        &mut self,
+        query: &mut QueryType,
+        read_options: Option<ReadOptions>,
+    ) -> Result<(Vec<EntityResult>, NextBatch), CloudDatastoreError> {
         let request = RunQueryRequest {
             project_id: self.project_id.clone(),
             database_id: self.database_id.clone(),
-            query_type: Some(QueryType::Query(Query {
-                kind: vec![KindExpression {
-                    name: T::kind().to_string(),
-                }],
-                ..Default::default()
-            })),
+            read_options,
+            query_type: Some(query.clone()),
             ..Default::default()
         };
 
         let response = self.run_query(request).await?;
+        if let (QueryType::GqlQuery(_), Some(parsed)) = (&query, response.query) {
+            *query = QueryType::Query(parsed);
+        }
+
         let Some(batch) = response.batch else {
-            return Ok(vec![]);
+            return Ok((vec![], NextBatch::Done(None)));
         };
 
-        let entities = batch
-            .entity_results
-            .into_iter()
-            .filter_map(|found| found.entity)
-            .map(|entity| T::try_from_entity(entity))
-            .collect::<Result<Vec<T>, TryFromEntityError>>()?;
+        let more_results = batch.more_results();
+        if more_results == MoreResultsType::NoMoreResults {
+            return Ok((batch.entity_results, NextBatch::Done(None)));
+        }
 
-        Ok(entities)
+        let more = match query {
+            QueryType::Query(query) => advance_query(query, &batch),
+            _ => false,
+        };
+        if !more {
+            let cursor = Cursor::from(batch.end_cursor);
+            return Ok((batch.entity_results, NextBatch::Done(Some(cursor))));
+        }
+
+        debug!(
+            returned = batch.entity_results.len(),
+            "Query not finished, more batches available."
+        );
+        Ok((batch.entity_results, NextBatch::More))
+    }
+
+    ///
+    /// Create a GQL query returning entities of type `T`. Bindings for the parameters of the query
+    /// are added to the returned [`GqlQueryBuilder`] before fetching the results.
+    ///
+    /// ```ignore
+    /// let books: Vec<Book> = datastore
+    ///     .gql("SELECT * FROM Book WHERE year > @year")
+    ///     .bind("year", 2000)
+    ///     .fetch()
+    ///     .await?;
+    /// ```
+    ///
+    pub fn gql<T: TryFromEntity>(
+        &mut self,
+        query_string: impl Into<String>,
+    ) -> GqlQueryBuilder<'_, T> {
+        GqlQueryBuilder::new(self, query_string.into())
+    }
+
+    ///
+    /// Apply server-side property transforms, such as increments, to the entity with `key`. See
+    /// [`TransformBuilder`].
+    ///
+    pub fn transform(&mut self, key: impl Into<Key>) -> TransformBuilder<'_> {
+        TransformBuilder::new(self, key.into())
     }
 
     /// Run a query. The provided query has the project_id set to the project_id of the Datastore instance.
@@ -313,6 +1168,363 @@ impl Datastore {
         request.database_id = self.database_id.clone();
         Ok(self.service.run_query(request).await?.into_inner())
     }
+
+    /// Run an aggregation query. The provided query has the project_id set to the project_id of
+    /// the Datastore instance.
+    pub async fn run_aggregation_query(
+        &mut self,
+        mut request: RunAggregationQueryRequest,
+    ) -> Result<RunAggregationQueryResponse, CloudDatastoreError> {
+        request.project_id = self.project_id.clone();
+        request.database_id = self.database_id.clone();
+        Ok(self
+            .service
+            .run_aggregation_query(request)
+            .await?
+            .into_inner())
+    }
+
+    ///
+    /// Run an aggregation query, usually created with an [`AggregationQueryBuilder`], and return
+    /// its result. Individual aggregations are read from the result by alias.
+    ///
+    pub async fn aggregate(
+        &mut self,
+        query: impl Into<AggregationQuery>,
+    ) -> Result<AggregationResult, CloudDatastoreError> {
+        let request = RunAggregationQueryRequest {
+            query_type: Some(AggregationQueryType::AggregationQuery(query.into())),
+            ..Default::default()
+        };
+
+        let response = self.run_aggregation_query(request).await?;
+        let result = response
+            .batch
+            .and_then(|batch| batch.aggregation_results.into_iter().next())
+            .unwrap_or_default();
+        Ok(result)
+    }
+
+    /// Count the entities matching a query.
+    pub async fn count(&mut self, query: impl Into<Query>) -> Result<i64, CloudDatastoreError> {
+        let query = AggregationQuery::builder(query).count("count");
+        Ok(self.aggregate(query).await?.count("count")?)
+    }
+
+    /// Sum the values of `property` over the entities matching a query.
+    pub async fn sum(
+        &mut self,
+        query: impl Into<Query>,
+        property: &str,
+    ) -> Result<Numeric, CloudDatastoreError> {
+        let query = AggregationQuery::builder(query).sum("sum", property);
+        Ok(self.aggregate(query).await?.sum("sum")?)
+    }
+
+    /// Average the values of `property` over the entities matching a query. Returns `None` when
+    /// no entity has a numeric value for `property`.
+    pub async fn avg(
+        &mut self,
+        query: impl Into<Query>,
+        property: &str,
+    ) -> Result<Option<f64>, CloudDatastoreError> {
+        let query = AggregationQuery::builder(query).avg("avg", property);
+        Ok(self.aggregate(query).await?.avg("avg")?)
+    }
+
+    ///
+    /// Explain how Datastore plans to run a query, including the indexes it would use.
+    ///
+    /// When `analyze` is true the query is also executed, and the report includes execution
+    /// statistics such as the number of results returned and billable read operations. Datastore
+    /// only sends these statistics with the last batch of results, so all batches are fetched
+    /// until the query is finished. The results themselves are discarded.
+    ///
+    pub async fn explain(
+        &mut self,
+        query: impl Into<Query>,
+        analyze: bool,
+    ) -> Result<ExplainReport, CloudDatastoreError> {
+        let mut query = query.into();
+        let mut metrics: Option<ExplainMetrics> = None;
+
+        loop {
+            let request = RunQueryRequest {
+                explain_options: Some(ExplainOptions { analyze }),
+                query_type: Some(QueryType::Query(query.clone())),
+                ..Default::default()
+            };
+
+            let response = self.run_query(request).await?;
+            if let Some(mut latest) = response.explain_metrics {
+                // Keep the plan if only an earlier response included it.
+                if latest.plan_summary.is_none() {
+                    latest.plan_summary = metrics.and_then(|m| m.plan_summary);
+                }
+                metrics = Some(latest);
+            }
+
+            match response.batch {
+                Some(batch) if analyze && advance_query(&mut query, &batch) => {}
+                _ => break,
+            }
+        }
+
+        Ok(metrics.map(ExplainReport::from).unwrap_or_default())
+    }
+
+    ///
+    /// Explain how Datastore plans to run an aggregation query. See [`Datastore::explain`].
+    ///
+    /// Aggregation results are always returned in a single batch, so the statistics of an
+    /// analyzed aggregation are complete in its only response.
+    ///
+    pub async fn explain_aggregation(
+        &mut self,
+        query: impl Into<AggregationQuery>,
+        analyze: bool,
+    ) -> Result<ExplainReport, CloudDatastoreError> {
+        let request = RunAggregationQueryRequest {
+            explain_options: Some(ExplainOptions { analyze }),
+            query_type: Some(AggregationQueryType::AggregationQuery(query.into())),
+            ..Default::default()
+        };
+
+        let response = self.run_aggregation_query(request).await?;
+        Ok(response
+            .explain_metrics
+            .map(ExplainReport::from)
+            .unwrap_or_default())
+    }
+
+    /// Run a query against the entities as they were at `read_time`. Any `read_options` set on the
+    /// request are replaced.
+    pub async fn run_query_at(
+        &mut self,
+        mut request: RunQueryRequest,
+        read_time: Timestamp,
+    ) -> Result<RunQueryResponse, CloudDatastoreError> {
+        request.read_options = read_time_options(read_time);
+        self.run_query(request).await
+    }
+
+    ///
+    /// Begin a new read-write transaction.
+    ///
+    /// Reads made through the returned [`Transaction`] happen inside the transaction, and writes
+    /// are buffered until [`Transaction::commit`] is called. A transaction that is dropped without
+    /// being committed is rolled back.
+    ///
+    pub async fn begin_transaction(&mut self) -> Result<Transaction, CloudDatastoreError> {
+        self.begin_transaction_with_options(TransactionOptions {
+            mode: Some(TransactionMode::ReadWrite(Default::default())),
+        })
+        .await
+    }
+
+    ///
+    /// Begin a new read-only transaction.
+    ///
+    /// All reads made through the returned [`Transaction`] observe the same consistent snapshot,
+    /// taken at `read_time` when provided, or when the transaction begins otherwise.
+    ///
+    pub async fn begin_read_only_transaction(
+        &mut self,
+        read_time: Option<Timestamp>,
+    ) -> Result<Transaction, CloudDatastoreError> {
+        self.begin_transaction_with_options(TransactionOptions {
+            mode: Some(TransactionMode::ReadOnly(ReadOnly { read_time })),
+        })
+        .await
+    }
+
+    ///
+    /// Begin a new transaction using the provided `TransactionOptions`.
+    ///
+    pub async fn begin_transaction_with_options(
+        &mut self,
+        options: TransactionOptions,
+    ) -> Result<Transaction, CloudDatastoreError> {
+        let request = BeginTransactionRequest {
+            project_id: self.project_id.clone(),
+            database_id: self.database_id.clone(),
+            transaction_options: Some(options),
+        };
+
+        let response = self.service.begin_transaction(request).await?.into_inner();
+        Ok(Transaction::new(self.clone(), response.transaction))
+    }
+
+    ///
+    /// Run `f` inside a read-write transaction and commit it, using the default [`RetryPolicy`].
+    ///
+    /// See [`Datastore::run_in_transaction_with_policy`].
+    ///
+    pub async fn run_in_transaction<F, R, E>(&mut self, f: F) -> Result<R, E>
+    where
+        F: for<'t> FnMut(&'t mut Transaction) -> BoxFuture<'t, Result<R, E>>,
+        E: From<CloudDatastoreError>,
+    {
+        self.run_in_transaction_with_policy(&RetryPolicy::default(), f)
+            .await
+    }
+
+    ///
+    /// Run `f` inside a read-write transaction and commit it.
+    ///
+    /// If the commit fails with `ABORTED` because of contention, the transaction is retried
+    /// according to `policy`, passing the failed transaction as `previous_transaction`. If `f`
+    /// returns an error, the transaction is rolled back and the error is returned without retrying.
+    ///
+    /// ```ignore
+    /// let count = datastore
+    ///     .run_in_transaction(|tx| {
+    ///         Box::pin(async move {
+    ///             let counter: Option<Counter> = tx.lookup_entity(key.clone()).await?;
+    ///             let counter = counter.unwrap_or_default().incremented();
+    ///             let count = counter.count;
+    ///             tx.upsert_entity(counter);
+    ///             Ok::<_, CloudDatastoreError>(count)
+    ///         })
+    ///     })
+    ///     .await?;
+    /// ```
+    ///
+    pub async fn run_in_transaction_with_policy<F, R, E>(
+        &mut self,
+        policy: &RetryPolicy,
+        mut f: F,
+    ) -> Result<R, E>
+    where
+        F: for<'t> FnMut(&'t mut Transaction) -> BoxFuture<'t, Result<R, E>>,
+        E: From<CloudDatastoreError>,
+    {
+        let mut previous_transaction = vec![];
+        let mut attempt = 1;
+
+        loop {
+            let mut transaction = self
+                .begin_transaction_with_options(TransactionOptions {
+                    mode: Some(TransactionMode::ReadWrite(ReadWrite {
+                        previous_transaction,
+                    })),
+                })
+                .await?;
+
+            let result = match f(&mut transaction).await {
+                Ok(result) => result,
+                Err(e) => {
+                    if let Err(rollback_error) = transaction.rollback().await {
+                        debug!(error = %rollback_error, "Failed to roll back transaction.");
+                    }
+                    return Err(e);
+                }
+            };
+
+            previous_transaction = transaction.id().to_vec();
+            match transaction.commit().await {
+                Ok(_) => return Ok(result),
+                Err(CloudDatastoreError::GrcpError(status))
+                    if status.code() == Code::Aborted && attempt < policy.max_attempts =>
+                {
+                    let backoff = policy.backoff(attempt);
+                    debug!(attempt, ?backoff, "Transaction aborted, retrying.");
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// The identity of `key` for matching lookup results to requested keys. Datastore fills in the
+/// project and database of the keys it returns, so only the namespace and path are compared.
+fn lookup_identity(key: &Key) -> Key {
+    let namespace_id = key
+        .partition_id
+        .as_ref()
+        .map(|p| p.namespace_id.clone())
+        .unwrap_or_default();
+    Key {
+        partition_id: (!namespace_id.is_empty()).then(|| PartitionId {
+            namespace_id,
+            ..Default::default()
+        }),
+        path: key.path.clone(),
+    }
+}
+
+/// A key of `kind` without id or name, under `parent` and in the same partition as it if given.
+fn incomplete_key(kind: String, parent: Option<Key>) -> Key {
+    let mut key = parent.unwrap_or_default();
+    key.path.push(PathElement {
+        kind,
+        id_type: None,
+    });
+    key
+}
+
+fn read_time_options(read_time: Timestamp) -> Option<ReadOptions> {
+    Some(ReadOptions {
+        consistency_type: Some(ConsistencyType::ReadTime(read_time)),
+    })
+}
+
+fn property_mask(fields: impl IntoIterator<Item = impl Into<String>>) -> PropertyMask {
+    PropertyMask {
+        paths: fields.into_iter().map(Into::into).collect(),
+    }
+}
+
+fn mutation(operation: Operation) -> Mutation {
+    Mutation {
+        operation: Some(operation),
+        ..Default::default()
+    }
+}
+
+fn checked_mutation(operation: Operation, strategy: ConflictDetectionStrategy) -> Mutation {
+    Mutation {
+        operation: Some(operation),
+        conflict_detection_strategy: Some(strategy),
+        ..Default::default()
+    }
+}
+
+/// Whether more batches of query results are available after the current one.
+enum NextBatch {
+    More,
+    /// The query is finished. The cursor is set if there may be more results after the limit or
+    /// end cursor of the query.
+    Done(Option<Cursor>),
+}
+
+/// Move `query` past `batch`, so that running it again returns the next batch. Returns false if
+/// the query is finished, because there are no more results or its limit was reached.
+fn advance_query(query: &mut Query, batch: &QueryResultBatch) -> bool {
+    if batch.more_results() != MoreResultsType::NotFinished {
+        return false;
+    }
+    if let Some(limit) = query.limit.as_mut() {
+        *limit -= batch.entity_results.len() as i32;
+        if *limit <= 0 {
+            return false;
+        }
+    }
+    query.offset = (query.offset - batch.skipped_results).max(0);
+    query.start_cursor = batch.end_cursor.clone();
+    true
+}
+
+fn decode_entity_results<T: TryFromEntity>(
+    entity_results: Vec<EntityResult>,
+) -> Result<Vec<T>, TryFromEntityError> {
+    entity_results
+        .into_iter()
+        .filter_map(|found| found.entity)
+        .map(|entity| T::try_from_entity(entity))
+        .collect()
 }
 
 /// Builder for creating an entity.
@@ -339,6 +1551,12 @@ impl EntityBuilder {
         self.with_key(key)
     }
 
+    /// Set an incomplete key of `kind`, whose numeric id is allocated by Datastore when the entity
+    /// is inserted. See [`Datastore::insert_entities_returning_keys`].
+    pub fn with_incomplete_key<T: Into<String>>(self, kind: T) -> Self {
+        self.with_key(incomplete_key(kind.into(), None))
+    }
+
     /// Set the key of the entity.
     pub fn with_key(mut self, key: Key) -> Self {
         self.entity.key = Some(key);
@@ -442,6 +1660,14 @@ impl EntityBuilder {
         self
     }
 
+    /// Add a vector embedding property to the entity.
+    pub fn add_vector<T: Into<String>, V: Into<Vector>>(mut self, name: T, value: V) -> Self {
+        self.entity
+            .properties
+            .insert(name.into(), value.into().into());
+        self
+    }
+
     /// Builds the entity.
     pub fn build(self) -> Entity {
         self.entity
@@ -480,6 +1706,16 @@ impl Entity {
         }
     }
 
+    /// The key of the entity as a [`TypedKey`], failing if it is not of the kind of `T`.
+    pub fn req_typed_key<T: Kind>(&self) -> Result<TypedKey<T>, EntityValueError> {
+        let key = self
+            .key
+            .clone()
+            .ok_or(EntityValueError("Missing Key".to_string()))?;
+
+        TypedKey::try_from(key).map_err(|e| EntityValueError(e.to_string()))
+    }
+
     pub fn req_string(&self, name: &str) -> Result<String, EntityValueError> {
         self.opt_string(name)
             .and_then(|v| v.ok_or(EntityValueError("missing required field".to_string())))
@@ -566,6 +1802,30 @@ impl Entity {
         let result = self.opt_string_array(name)?;
         result.ok_or_else(|| EntityValueError(format!("Entity missing required field '{}'", name)))
     }
+
+    pub fn opt_vector(&self, name: &str) -> Result<Option<Vector>, EntityValueError> {
+        let Some(value) = self.properties.get(name) else {
+            return Ok(None);
+        };
+
+        match &value.value_type {
+            Some(ValueType::ArrayValue(array)) if value.meaning == VECTOR_MEANING => array
+                .values
+                .iter()
+                .map(|v| match v.value_type {
+                    Some(ValueType::DoubleValue(d)) => Ok(d),
+                    _ => Err(EntityValueError(format!("Field {name} is not a vector"))),
+                })
+                .collect::<Result<Vec<f64>, EntityValueError>>()
+                .map(|values| Some(Vector(values))),
+            _ => Err(EntityValueError(format!("Field {name} is not a vector"))),
+        }
+    }
+
+    pub fn req_vector(&self, name: &str) -> Result<Vector, EntityValueError> {
+        self.opt_vector(name)?
+            .ok_or_else(|| EntityValueError(format!("Entity missing required field '{}'", name)))
+    }
 }
 
 #[derive(Debug)]
@@ -579,28 +1839,87 @@ impl Display for KeyError {
     }
 }
 
-impl Key {
-    pub fn kind(&self) -> Result<&str, KeyError> {
-        if self.path.is_empty() {
-            return Err(KeyError("Key has no path".to_string()));
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Some(partition_id) = &self.partition_id {
+            partition_id.project_id.hash(state);
+            partition_id.database_id.hash(state);
+            partition_id.namespace_id.hash(state);
         }
+        for element in &self.path {
+            element.kind.hash(state);
+            match &element.id_type {
+                Some(IdType::Id(id)) => id.hash(state),
+                Some(IdType::Name(name)) => name.hash(state),
+                None => {}
+            }
+        }
+    }
+}
 
-        Ok(&self.path[0].kind)
+impl Key {
+    /// The kind of the entity the key refers to. See also [`Key::leaf_kind`].
+    pub fn kind(&self) -> Result<&str, KeyError> {
+        self.leaf_kind()
+            .ok_or(KeyError("Key has no path".to_string()))
     }
 
+    /// The name of the entity the key refers to. See also [`Key::leaf_name`].
     pub fn name(&self) -> Result<&str, KeyError> {
         if self.path.is_empty() {
             return Err(KeyError("Key has no path".to_string()));
         }
 
-        let id_type = self.path[0]
-            .id_type
-            .as_ref()
-            .ok_or(KeyError("Key has no name".to_string()))?;
+        self.leaf_name()
+            .ok_or(KeyError("Key has no name".to_string()))
+    }
+}
 
-        match id_type {
-            IdType::Name(name) => Ok(name),
-            _ => Err(KeyError("Key has no name".to_string())),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(more_results: MoreResultsType, returned: usize, skipped: i32) -> QueryResultBatch {
+        QueryResultBatch {
+            more_results: more_results as i32,
+            entity_results: vec![EntityResult::default(); returned],
+            skipped_results: skipped,
+            end_cursor: b"cursor".to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn advance_query_follows_unfinished_batches() {
+        let mut query = Query {
+            limit: Some(10),
+            offset: 5,
+            ..Default::default()
+        };
+        assert!(advance_query(
+            &mut query,
+            &batch(MoreResultsType::NotFinished, 3, 2)
+        ));
+        assert_eq!(query.limit, Some(7));
+        assert_eq!(query.offset, 3);
+        assert_eq!(query.start_cursor, b"cursor");
+
+        // The limit is reached.
+        assert!(!advance_query(
+            &mut query,
+            &batch(MoreResultsType::NotFinished, 7, 0)
+        ));
+
+        for more_results in [
+            MoreResultsType::NoMoreResults,
+            MoreResultsType::MoreResultsAfterLimit,
+            MoreResultsType::MoreResultsAfterCursor,
+        ] {
+            let mut query = Query::default();
+            assert!(!advance_query(&mut query, &batch(more_results, 1, 0)));
+            assert!(query.start_cursor.is_empty());
         }
     }
 }
@@ -0,0 +1,304 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use crate::google::datastore::v1::{
+    composite_filter::Operator as CompositeOperator, filter::FilterType,
+    find_nearest::DistanceMeasure, property_filter::Operator, property_order::Direction,
+    value::ValueType, CompositeFilter, Filter, Key, KindExpression, Projection, PropertyFilter,
+    PropertyOrder, PropertyReference, Query, Value,
+};
+use crate::{Kind, Vector};
+
+impl Query {
+    /// Create a builder for a query over entities of kind `T`.
+    pub fn kind<T: Kind>() -> QueryBuilder {
+        QueryBuilder::new(T::kind())
+    }
+
+    /// Create a builder for a query over entities of the given kind.
+    pub fn builder<T: Into<String>>(kind: T) -> QueryBuilder {
+        QueryBuilder::new(kind)
+    }
+
+    /// Create a builder for a query over entities of all kinds.
+    pub fn kindless() -> QueryBuilder {
+        QueryBuilder {
+            query: Default::default(),
+        }
+    }
+}
+
+impl Filter {
+    /// Create a filter on a single property.
+    pub fn property<T: Into<String>, V: Into<ValueType>>(name: T, op: Operator, value: V) -> Self {
+        Filter {
+            filter_type: Some(FilterType::PropertyFilter(PropertyFilter {
+                property: Some(property_reference(name)),
+                op: op as i32,
+                value: Some(Value {
+                    value_type: Some(value.into()),
+                    ..Default::default()
+                }),
+            })),
+        }
+    }
+
+    /// Create a filter matching entities that have `key` as an ancestor.
+    pub fn has_ancestor(key: Key) -> Self {
+        Filter::property("__key__", Operator::HasAncestor, key)
+    }
+
+    /// Create a filter matching entities that satisfy all of `filters`.
+    pub fn and(filters: Vec<Filter>) -> Self {
+        Filter::composite(CompositeOperator::And, filters)
+    }
+
+    /// Create a filter matching entities that satisfy at least one of `filters`.
+    pub fn or(filters: Vec<Filter>) -> Self {
+        Filter::composite(CompositeOperator::Or, filters)
+    }
+
+    fn composite(op: CompositeOperator, filters: Vec<Filter>) -> Self {
+        Filter {
+            filter_type: Some(FilterType::CompositeFilter(CompositeFilter {
+                op: op as i32,
+                filters,
+            })),
+        }
+    }
+
+    /// Combine this filter with `other`, flattening nested composites using the same operator.
+    fn combine(self, op: CompositeOperator, other: Filter) -> Self {
+        match self.filter_type {
+            Some(FilterType::CompositeFilter(mut composite)) if composite.op == op as i32 => {
+                composite.filters.push(other);
+                Filter {
+                    filter_type: Some(FilterType::CompositeFilter(composite)),
+                }
+            }
+            None => other,
+            filter_type => Filter::composite(op, vec![Filter { filter_type }, other]),
+        }
+    }
+}
+
+/// Builder for creating a [`Query`].
+#[derive(Clone, Debug)]
+pub struct QueryBuilder {
+    query: Query,
+}
+
+impl QueryBuilder {
+    pub fn new<T: Into<String>>(kind: T) -> Self {
+        QueryBuilder {
+            query: Query {
+                kind: vec![KindExpression { name: kind.into() }],
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Add a property filter, combined with any existing filter using `AND`.
+    pub fn filter<T: Into<String>, V: Into<ValueType>>(
+        self,
+        name: T,
+        op: Operator,
+        value: V,
+    ) -> Self {
+        self.and(Filter::property(name, op, value))
+    }
+
+    /// Restrict the results to descendants of `key`.
+    pub fn has_ancestor(self, key: Key) -> Self {
+        self.and(Filter::has_ancestor(key))
+    }
+
+    /// Combine the existing filter with `filter` using `AND`.
+    pub fn and(mut self, filter: Filter) -> Self {
+        self.query.filter = Some(
+            self.current_filter()
+                .combine(CompositeOperator::And, filter),
+        );
+        self
+    }
+
+    /// Combine the existing filter with `filter` using `OR`.
+    pub fn or(mut self, filter: Filter) -> Self {
+        self.query.filter = Some(self.current_filter().combine(CompositeOperator::Or, filter));
+        self
+    }
+
+    fn current_filter(&mut self) -> Filter {
+        self.query.filter.take().unwrap_or_default()
+    }
+
+    /// Order the results by a property, ascending.
+    pub fn order_by_asc<T: Into<String>>(self, name: T) -> Self {
+        self.order_by(name, Direction::Ascending)
+    }
+
+    /// Order the results by a property, descending.
+    pub fn order_by_desc<T: Into<String>>(self, name: T) -> Self {
+        self.order_by(name, Direction::Descending)
+    }
+
+    /// Order the results by a property. Orders are applied in the sequence they are added.
+    pub fn order_by<T: Into<String>>(mut self, name: T, direction: Direction) -> Self {
+        self.query.order.push(PropertyOrder {
+            property: Some(property_reference(name)),
+            direction: direction as i32,
+        });
+        self
+    }
+
+    /// Only return the given properties.
+    pub fn project<I: IntoIterator<Item = T>, T: Into<String>>(mut self, names: I) -> Self {
+        self.query
+            .projection
+            .extend(names.into_iter().map(|name| Projection {
+                property: Some(property_reference(name)),
+            }));
+        self
+    }
+
+    /// Only return the first result for each distinct combination of values of the given
+    /// properties.
+    pub fn distinct_on<I: IntoIterator<Item = T>, T: Into<String>>(mut self, names: I) -> Self {
+        self.query
+            .distinct_on
+            .extend(names.into_iter().map(property_reference));
+        self
+    }
+
+    /// Set the maximum number of results to return.
+    pub fn limit(mut self, limit: i32) -> Self {
+        self.query.limit = Some(limit);
+        self
+    }
+
+    /// Set the number of results to skip.
+    pub fn offset(mut self, offset: i32) -> Self {
+        self.query.offset = offset;
+        self
+    }
+
+    /// Start returning results at the given cursor.
+    pub fn start_cursor<C: Into<Vec<u8>>>(mut self, cursor: C) -> Self {
+        self.query.start_cursor = cursor.into();
+        self
+    }
+
+    /// Stop returning results at the given cursor.
+    pub fn end_cursor<C: Into<Vec<u8>>>(mut self, cursor: C) -> Self {
+        self.query.end_cursor = cursor.into();
+        self
+    }
+
+    /// Return the `limit` entities whose vector property `name` is nearest to `query_vector`. The
+    /// nearest neighbour search is applied after any filters of the query.
+    pub fn find_nearest<T: Into<String>, V: Into<Vector>>(
+        mut self,
+        name: T,
+        query_vector: V,
+        measure: DistanceMeasure,
+        limit: i32,
+    ) -> Self {
+        let find_nearest = self.query.find_nearest.get_or_insert_with(Default::default);
+        find_nearest.vector_property = Some(property_reference(name));
+        find_nearest.query_vector = Some(query_vector.into().into());
+        find_nearest.distance_measure = measure as i32;
+        find_nearest.limit = Some(limit);
+        self
+    }
+
+    /// Exclude nearest neighbours further than `threshold` from the query vector. For
+    /// `DotProduct`, where larger values are more similar, results below `threshold` are excluded.
+    pub fn distance_threshold(mut self, threshold: f64) -> Self {
+        let find_nearest = self.query.find_nearest.get_or_insert_with(Default::default);
+        find_nearest.distance_threshold = Some(threshold);
+        self
+    }
+
+    /// Return the distance of each nearest neighbour from the query vector in property `name`.
+    pub fn distance_result_property<T: Into<String>>(mut self, name: T) -> Self {
+        let find_nearest = self.query.find_nearest.get_or_insert_with(Default::default);
+        find_nearest.distance_result_property = name.into();
+        self
+    }
+
+    /// Builds the query.
+    pub fn build(self) -> Query {
+        self.query
+    }
+}
+
+impl From<QueryBuilder> for Query {
+    fn from(builder: QueryBuilder) -> Self {
+        builder.build()
+    }
+}
+
+fn property_reference<T: Into<String>>(name: T) -> PropertyReference {
+    PropertyReference { name: name.into() }
+}
+
+///
+/// An opaque position in the results of a query, used to resume the query from where a previous
+/// page of results ended.
+///
+/// A cursor can be converted to and from a URL-safe string, making it suitable for passing to and
+/// from web clients.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cursor(Vec<u8>);
+
+impl Cursor {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Cursor {
+    fn from(bytes: Vec<u8>) -> Self {
+        Cursor(bytes)
+    }
+}
+
+impl From<Cursor> for Vec<u8> {
+    fn from(cursor: Cursor) -> Self {
+        cursor.0
+    }
+}
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", URL_SAFE_NO_PAD.encode(&self.0))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = CursorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        URL_SAFE_NO_PAD
+            .decode(s)
+            .map(Cursor)
+            .map_err(|e| CursorError(e.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub struct CursorError(String);
+
+impl Error for CursorError {}
+
+impl Display for CursorError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Invalid cursor: {}", self.0)
+    }
+}
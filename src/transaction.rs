@@ -0,0 +1,271 @@
+use std::time::Duration;
+
+use rand::Rng;
+use tracing::debug;
+
+use crate::google::datastore::v1::{
+    commit_request::{Mode as CommitMode, TransactionSelector},
+    mutation::Operation,
+    read_options::ConsistencyType,
+    CommitRequest, CommitResponse, Entity, Key, Mutation, Query, ReadOptions, RollbackRequest,
+    RunQueryRequest, RunQueryResponse,
+};
+use crate::{CloudDatastoreError, Datastore, EntityKey, Kind, TryFromEntity};
+
+///
+/// A Datastore transaction, created with [`Datastore::begin_transaction`].
+///
+/// Lookups and queries run inside the transaction. Mutations are buffered locally and sent to
+/// Datastore in a single request when [`Transaction::commit`] is called. If the transaction is
+/// dropped before being committed or rolled back, a rollback is issued in the background.
+///
+pub struct Transaction {
+    datastore: Datastore,
+    id: Vec<u8>,
+    mutations: Vec<Mutation>,
+    finished: bool,
+}
+
+impl Transaction {
+    pub(crate) fn new(datastore: Datastore, id: Vec<u8>) -> Self {
+        Transaction {
+            datastore,
+            id,
+            mutations: vec![],
+            finished: false,
+        }
+    }
+
+    /// The opaque identifier of the transaction.
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+
+    fn read_options(&self) -> Option<ReadOptions> {
+        Some(ReadOptions {
+            consistency_type: Some(ConsistencyType::Transaction(self.id.clone())),
+        })
+    }
+
+    ///
+    /// Load an entity inside the transaction.
+    ///
+    pub async fn lookup_entity<T: TryFromEntity>(
+        &mut self,
+        key: impl EntityKey<T>,
+    ) -> Result<Option<T>, CloudDatastoreError> {
+        let read_options = self.read_options();
+        self.datastore
+            .lookup_entity_with_options(key.into_key(), read_options)
+            .await
+    }
+
+    ///
+    /// Load entities by key inside the transaction. See [`Datastore::lookup_entities`].
+    ///
+    pub async fn lookup_entities<T: TryFromEntity>(
+        &mut self,
+        keys: Vec<impl EntityKey<T>>,
+    ) -> Result<Vec<Option<T>>, CloudDatastoreError> {
+        let keys = keys.into_iter().map(EntityKey::into_key).collect();
+        let read_options = self.read_options();
+        self.datastore
+            .lookup_entities_with_options(keys, read_options)
+            .await
+    }
+
+    /// Load all entities of a given kind inside the transaction.
+    pub async fn load_entities<T: TryFromEntity + Kind>(
+        &mut self,
+    ) -> Result<Vec<T>, CloudDatastoreError> {
+        let read_options = self.read_options();
+        self.datastore
+            .load_entities_with_options(read_options)
+            .await
+    }
+
+    /// Load the entities matching a query inside the transaction.
+    pub async fn query_entities<T: TryFromEntity>(
+        &mut self,
+        query: impl Into<Query>,
+    ) -> Result<Vec<T>, CloudDatastoreError> {
+        let read_options = self.read_options();
+        self.datastore
+            .query_entities_with_options(query.into(), read_options)
+            .await
+    }
+
+    /// Run a query inside the transaction. Any `read_options` set on the request are replaced.
+    pub async fn run_query(
+        &mut self,
+        mut request: RunQueryRequest,
+    ) -> Result<RunQueryResponse, CloudDatastoreError> {
+        request.read_options = self.read_options();
+        self.datastore.run_query(request).await
+    }
+
+    ///
+    /// Buffer an upsert of an entity, to be applied on commit.
+    ///
+    pub fn upsert_entity(&mut self, entity: impl Into<Entity>) {
+        self.mutations.push(Mutation {
+            operation: Some(Operation::Upsert(entity.into())),
+            ..Default::default()
+        });
+    }
+
+    ///
+    /// Buffer upserts of entities, to be applied on commit.
+    ///
+    pub fn upsert_entities(&mut self, entities: Vec<impl Into<Entity>>) {
+        for entity in entities {
+            self.upsert_entity(entity);
+        }
+    }
+
+    ///
+    /// Buffer an insert of an entity, to be applied on commit. The commit fails with
+    /// [`CloudDatastoreError::AlreadyExists`] if an entity with the same key already exists.
+    ///
+    pub fn insert_entity(&mut self, entity: impl Into<Entity>) {
+        self.mutations.push(Mutation {
+            operation: Some(Operation::Insert(entity.into())),
+            ..Default::default()
+        });
+    }
+
+    ///
+    /// Buffer an update of an entity, to be applied on commit. The commit fails with
+    /// [`CloudDatastoreError::NotFound`] if no entity with the same key exists.
+    ///
+    pub fn update_entity(&mut self, entity: impl Into<Entity>) {
+        self.mutations.push(Mutation {
+            operation: Some(Operation::Update(entity.into())),
+            ..Default::default()
+        });
+    }
+
+    ///
+    /// Buffer a delete of an entity, to be applied on commit.
+    ///
+    pub fn delete_entity(&mut self, key: impl Into<Key>) {
+        self.mutations.push(Mutation {
+            operation: Some(Operation::Delete(key.into())),
+            ..Default::default()
+        });
+    }
+
+    ///
+    /// Buffer deletes of entities, to be applied on commit.
+    ///
+    pub fn delete_entities(&mut self, keys: Vec<impl Into<Key>>) {
+        for key in keys {
+            self.delete_entity(key);
+        }
+    }
+
+    ///
+    /// Commit the transaction, applying all buffered mutations atomically.
+    ///
+    pub async fn commit(mut self) -> Result<CommitResponse, CloudDatastoreError> {
+        self.finished = true;
+
+        let request = CommitRequest {
+            project_id: self.datastore.project_id.clone(),
+            database_id: self.datastore.database_id.clone(),
+            mode: CommitMode::Transactional as i32,
+            transaction_selector: Some(TransactionSelector::Transaction(std::mem::take(
+                &mut self.id,
+            ))),
+            mutations: std::mem::take(&mut self.mutations),
+        };
+
+        self.datastore
+            .service
+            .commit(request)
+            .await
+            .map(|response| response.into_inner())
+            .map_err(|status| CloudDatastoreError::from(status).classify_mutation_error())
+    }
+
+    ///
+    /// Roll back the transaction, discarding all buffered mutations.
+    ///
+    pub async fn rollback(mut self) -> Result<(), CloudDatastoreError> {
+        self.finished = true;
+        let request = self.rollback_request();
+        self.datastore.service.rollback(request).await?;
+        Ok(())
+    }
+
+    fn rollback_request(&mut self) -> RollbackRequest {
+        RollbackRequest {
+            project_id: self.datastore.project_id.clone(),
+            database_id: self.datastore.database_id.clone(),
+            transaction: std::mem::take(&mut self.id),
+        }
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            debug!("No tokio runtime available, unable to roll back dropped transaction.");
+            return;
+        };
+
+        let request = self.rollback_request();
+        let mut service = self.datastore.service.clone();
+        handle.spawn(async move {
+            if let Err(e) = service.rollback(request).await {
+                debug!(error = %e, "Failed to roll back dropped transaction.");
+            }
+        });
+    }
+}
+
+///
+/// Controls how [`Datastore::run_in_transaction_with_policy`] retries transactions that fail to
+/// commit because of contention.
+///
+/// The delay before attempt `n + 1` is `initial_backoff * multiplier^(n - 1)`, capped at
+/// `max_backoff`, with a random jitter of up to half of the delay subtracted from it.
+///
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts.
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after each attempt.
+    pub multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1) as i32;
+        let delay = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let delay = Duration::try_from_secs_f64(delay)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        let jitter = rand::thread_rng().gen_range(0.0..=0.5);
+        delay.mul_f64(1.0 - jitter)
+    }
+}
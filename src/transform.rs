@@ -0,0 +1,252 @@
+use prost_types::Timestamp;
+
+use crate::google::datastore::v1::{
+    commit_request::Mode as CommitMode,
+    mutation::Operation,
+    property_transform::{ServerValue, TransformType},
+    value::ValueType,
+    ArrayValue, Entity, Key, Mutation, PropertyMask, PropertyTransform, Value,
+};
+use crate::{CloudDatastoreError, Datastore, EntityMetadata, EntityValueError, Numeric};
+
+impl PropertyTransform {
+    /// Add `value`, an integer or a double, to the property.
+    pub fn increment<N: Into<String>, V: Into<ValueType>>(name: N, value: V) -> Self {
+        Self::new(name, TransformType::Increment(value_of(value)))
+    }
+
+    /// Set the property to the maximum of its current value and `value`.
+    pub fn maximum<N: Into<String>, V: Into<ValueType>>(name: N, value: V) -> Self {
+        Self::new(name, TransformType::Maximum(value_of(value)))
+    }
+
+    /// Set the property to the minimum of its current value and `value`.
+    pub fn minimum<N: Into<String>, V: Into<ValueType>>(name: N, value: V) -> Self {
+        Self::new(name, TransformType::Minimum(value_of(value)))
+    }
+
+    /// Set the property to the time at which the server processed the request.
+    pub fn set_server_time<N: Into<String>>(name: N) -> Self {
+        Self::new(
+            name,
+            TransformType::SetToServerValue(ServerValue::RequestTime as i32),
+        )
+    }
+
+    /// Append the elements of `values` that are not already present in the array property.
+    pub fn append_missing_elements<N: Into<String>, V: Into<ValueType>>(
+        name: N,
+        values: Vec<V>,
+    ) -> Self {
+        Self::new(name, TransformType::AppendMissingElements(array_of(values)))
+    }
+
+    /// Remove all elements equal to any of `values` from the array property.
+    pub fn remove_all_from_array<N: Into<String>, V: Into<ValueType>>(
+        name: N,
+        values: Vec<V>,
+    ) -> Self {
+        Self::new(name, TransformType::RemoveAllFromArray(array_of(values)))
+    }
+
+    fn new<N: Into<String>>(name: N, transform_type: TransformType) -> Self {
+        PropertyTransform {
+            property: name.into(),
+            transform_type: Some(transform_type),
+        }
+    }
+}
+
+fn value_of<V: Into<ValueType>>(value: V) -> Value {
+    Value {
+        value_type: Some(value.into()),
+        ..Default::default()
+    }
+}
+
+fn array_of<V: Into<ValueType>>(values: Vec<V>) -> ArrayValue {
+    ArrayValue {
+        values: values.into_iter().map(value_of).collect(),
+    }
+}
+
+///
+/// Builder for applying server-side property transforms to an entity, created with
+/// [`Datastore::transform`].
+///
+/// Transforms are applied in order, atomically, by Datastore. Without [`TransformBuilder::upsert`]
+/// only the transformed properties are written, and the entity is created if it does not exist,
+/// which makes transforms suitable for counters without a transaction:
+///
+/// ```ignore
+/// let results = datastore
+///     .transform(key)
+///     .increment("views", 1)
+///     .set_server_time("updated_at")
+///     .commit()
+///     .await?;
+/// let views = results.numeric("views")?;
+/// ```
+///
+pub struct TransformBuilder<'a> {
+    datastore: &'a mut Datastore,
+    key: Key,
+    entity: Option<Entity>,
+    transforms: Vec<PropertyTransform>,
+}
+
+impl<'a> TransformBuilder<'a> {
+    pub(crate) fn new(datastore: &'a mut Datastore, key: Key) -> Self {
+        TransformBuilder {
+            datastore,
+            key,
+            entity: None,
+            transforms: Vec::new(),
+        }
+    }
+
+    /// Upsert `entity` before applying the transforms. The key of the entity is replaced with the
+    /// key of the builder.
+    pub fn upsert(mut self, entity: impl Into<Entity>) -> Self {
+        self.entity = Some(entity.into());
+        self
+    }
+
+    /// Add a transform.
+    pub fn transform(mut self, transform: PropertyTransform) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Add `value`, an integer or a double, to the property.
+    pub fn increment<N: Into<String>, V: Into<ValueType>>(self, name: N, value: V) -> Self {
+        self.transform(PropertyTransform::increment(name, value))
+    }
+
+    /// Set the property to the maximum of its current value and `value`.
+    pub fn maximum<N: Into<String>, V: Into<ValueType>>(self, name: N, value: V) -> Self {
+        self.transform(PropertyTransform::maximum(name, value))
+    }
+
+    /// Set the property to the minimum of its current value and `value`.
+    pub fn minimum<N: Into<String>, V: Into<ValueType>>(self, name: N, value: V) -> Self {
+        self.transform(PropertyTransform::minimum(name, value))
+    }
+
+    /// Set the property to the time at which the server processed the request.
+    pub fn set_server_time<N: Into<String>>(self, name: N) -> Self {
+        self.transform(PropertyTransform::set_server_time(name))
+    }
+
+    /// Append the elements of `values` that are not already present in the array property.
+    pub fn append_missing_elements<N: Into<String>, V: Into<ValueType>>(
+        self,
+        name: N,
+        values: Vec<V>,
+    ) -> Self {
+        self.transform(PropertyTransform::append_missing_elements(name, values))
+    }
+
+    /// Remove all elements equal to any of `values` from the array property.
+    pub fn remove_all_from_array<N: Into<String>, V: Into<ValueType>>(
+        self,
+        name: N,
+        values: Vec<V>,
+    ) -> Self {
+        self.transform(PropertyTransform::remove_all_from_array(name, values))
+    }
+
+    /// Build the mutation applying the transforms.
+    pub fn build(self) -> Mutation {
+        transform_mutation(self.key, self.entity, self.transforms)
+    }
+
+    /// Commit the transforms, returning the values of the transformed properties.
+    pub async fn commit(self) -> Result<TransformResults, CloudDatastoreError> {
+        let properties = self.transforms.iter().map(|t| t.property.clone()).collect();
+        let datastore = self.datastore;
+        let mutation = transform_mutation(self.key, self.entity, self.transforms);
+        let response = datastore
+            .commit_mutations(vec![mutation], CommitMode::NonTransactional)
+            .await?;
+        let result = response.mutation_results.into_iter().next();
+        Ok(TransformResults {
+            metadata: result
+                .as_ref()
+                .map(EntityMetadata::from)
+                .unwrap_or_default(),
+            properties,
+            values: result.map(|r| r.transform_results).unwrap_or_default(),
+        })
+    }
+}
+
+/// An upsert of `entity`, or, without an entity, of an empty property mask, followed by
+/// `transforms`.
+pub(crate) fn transform_mutation(
+    key: Key,
+    entity: Option<Entity>,
+    transforms: Vec<PropertyTransform>,
+) -> Mutation {
+    let property_mask = entity.is_none().then(PropertyMask::default);
+    let entity = Entity {
+        key: Some(key),
+        ..entity.unwrap_or_default()
+    };
+    Mutation {
+        operation: Some(Operation::Upsert(entity)),
+        property_mask,
+        property_transforms: transforms,
+        ..Default::default()
+    }
+}
+
+///
+/// The values of the transformed properties after a commit of a [`TransformBuilder`].
+///
+/// Array transforms have a null result. If a property was transformed more than once, its value
+/// is the result of the last transform.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransformResults {
+    properties: Vec<String>,
+    values: Vec<Value>,
+    /// The metadata of the entity after the transforms were applied.
+    pub metadata: EntityMetadata,
+}
+
+impl TransformResults {
+    /// The value of the property after the transforms were applied.
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        let i = self.properties.iter().rposition(|p| p == name)?;
+        self.values.get(i)
+    }
+
+    /// The value of a property transformed with `increment`, `maximum` or `minimum`.
+    pub fn numeric(&self, name: &str) -> Result<Numeric, EntityValueError> {
+        match self.value_type(name)? {
+            Some(ValueType::IntegerValue(i)) => Ok(Numeric::Integer(*i)),
+            Some(ValueType::DoubleValue(d)) => Ok(Numeric::Double(*d)),
+            _ => Err(EntityValueError(format!("Property {name} is not numeric"))),
+        }
+    }
+
+    /// The value of a property transformed with `set_server_time`.
+    pub fn timestamp(&self, name: &str) -> Result<Timestamp, EntityValueError> {
+        match self.value_type(name)? {
+            Some(ValueType::TimestampValue(t)) => Ok(*t),
+            _ => Err(EntityValueError(format!(
+                "Property {name} is not a timestamp"
+            ))),
+        }
+    }
+
+    fn value_type(&self, name: &str) -> Result<Option<&ValueType>, EntityValueError> {
+        match self.value(name) {
+            Some(value) => Ok(value.value_type.as_ref()),
+            None => Err(EntityValueError(format!(
+                "Missing transform result for {name}"
+            ))),
+        }
+    }
+}
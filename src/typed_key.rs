@@ -0,0 +1,256 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use crate::google::datastore::v1::{
+    key::{path_element::IdType, PathElement},
+    Key,
+};
+use crate::{KeyBuilder, KeyError, Kind};
+
+///
+/// A key that can be used to load entities of type `T`.
+///
+/// Plain [`Key`]s can be used to load any type, while a [`TypedKey<T>`] can only be used to load
+/// `T`, so that loading an entity with the key of another kind does not compile.
+///
+pub trait EntityKey<T> {
+    fn into_key(self) -> Key;
+}
+
+impl<T> EntityKey<T> for Key {
+    fn into_key(self) -> Key {
+        self
+    }
+}
+
+impl<T> EntityKey<T> for KeyBuilder {
+    fn into_key(self) -> Key {
+        self.build()
+    }
+}
+
+impl<T: Kind> EntityKey<T> for TypedKey<T> {
+    fn into_key(self) -> Key {
+        self.key
+    }
+}
+
+///
+/// The key of an entity of type `T`, whose last path element always has the kind of `T`.
+///
+/// ```ignore
+/// let book_key = TypedKey::<Book>::name("book_one");
+/// let chapter_key = TypedKey::<Chapter>::id(12).with_parent(book_key.clone());
+///
+/// let book: Option<Book> = datastore.lookup_entity(book_key).await?;
+/// // Does not compile: a chapter key cannot load a book.
+/// let book: Option<Book> = datastore.lookup_entity(chapter_key).await?;
+/// ```
+///
+/// Keys read from Datastore are checked with `TryFrom<Key>`, which fails if the kind does not
+/// match.
+///
+pub struct TypedKey<T> {
+    key: Key,
+    _kind: PhantomData<fn() -> T>,
+}
+
+impl<T: Kind> TypedKey<T> {
+    /// A root key of kind `T` with a name.
+    pub fn name<N: Into<String>>(name: N) -> Self {
+        Self::leaf(Some(IdType::Name(name.into())))
+    }
+
+    /// A root key of kind `T` with a numeric id.
+    pub fn id(id: i64) -> Self {
+        Self::leaf(Some(IdType::Id(id)))
+    }
+
+    /// An incomplete root key of kind `T`, whose id is allocated when the entity is inserted.
+    pub fn incomplete() -> Self {
+        Self::leaf(None)
+    }
+
+    /// Place the key under `parent`, in the partition of the parent.
+    pub fn with_parent(mut self, parent: impl Into<Key>) -> Self {
+        let leaf = self.key.path.pop();
+        self.key = parent.into();
+        self.key.path.extend(leaf);
+        self
+    }
+
+    fn leaf(id_type: Option<IdType>) -> Self {
+        TypedKey {
+            key: Key {
+                path: vec![PathElement {
+                    kind: T::kind().to_string(),
+                    id_type,
+                }],
+                ..Default::default()
+            },
+            _kind: PhantomData,
+        }
+    }
+}
+
+impl<T> TypedKey<T> {
+    pub fn as_key(&self) -> &Key {
+        &self.key
+    }
+
+    /// The key of the parent entity, or `None` for a root entity.
+    pub fn parent(&self) -> Option<Key> {
+        self.key.parent()
+    }
+}
+
+impl<T: Kind> TryFrom<Key> for TypedKey<T> {
+    type Error = KeyError;
+
+    fn try_from(key: Key) -> Result<Self, Self::Error> {
+        match key.leaf_kind() {
+            Some(kind) if kind == T::kind() => Ok(TypedKey {
+                key,
+                _kind: PhantomData,
+            }),
+            _ => Err(KeyError(format!(
+                "Invalid Key Kind. Expected '{}'.",
+                T::kind()
+            ))),
+        }
+    }
+}
+
+impl<T> From<TypedKey<T>> for Key {
+    fn from(typed_key: TypedKey<T>) -> Self {
+        typed_key.key
+    }
+}
+
+impl<T> AsRef<Key> for TypedKey<T> {
+    fn as_ref(&self) -> &Key {
+        &self.key
+    }
+}
+
+impl<T> Clone for TypedKey<T> {
+    fn clone(&self) -> Self {
+        TypedKey {
+            key: self.key.clone(),
+            _kind: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for TypedKey<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_tuple("TypedKey").field(&self.key).finish()
+    }
+}
+
+impl<T> PartialEq for TypedKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T> Eq for TypedKey<T> {}
+
+impl<T> Hash for TypedKey<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{EntityBuilder, Kind};
+
+    use super::*;
+
+    struct Book;
+
+    impl Kind for Book {
+        fn kind() -> &'static str {
+            "Book"
+        }
+    }
+
+    struct Chapter;
+
+    impl Kind for Chapter {
+        fn kind() -> &'static str {
+            "Chapter"
+        }
+    }
+
+    #[test]
+    fn matching_leaf_kind_is_accepted() {
+        let key = Key::builder().name("Book", "book_one").build();
+        let typed_key = TypedKey::<Book>::try_from(key.clone()).unwrap();
+        assert_eq!(typed_key, TypedKey::name("book_one"));
+        assert_eq!(typed_key.as_key(), &key);
+
+        let key = Key::builder()
+            .name("Book", "book_one")
+            .id("Chapter", 12)
+            .build();
+        let typed_key = TypedKey::<Chapter>::try_from(key.clone()).unwrap();
+        assert_eq!(Key::from(typed_key), key);
+    }
+
+    #[test]
+    fn other_leaf_kind_is_rejected() {
+        // The root has the kind of the typed key, but the key refers to a chapter.
+        let key = Key::builder()
+            .name("Book", "book_one")
+            .id("Chapter", 12)
+            .build();
+        let error = TypedKey::<Book>::try_from(key).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid Key Kind. Expected 'Book'.");
+
+        assert!(TypedKey::<Book>::try_from(Key::default()).is_err());
+    }
+
+    #[test]
+    fn req_typed_key_checks_the_kind() {
+        let key = Key::builder()
+            .name("Book", "book_one")
+            .id("Chapter", 12)
+            .build();
+        let entity = EntityBuilder::new().with_key(key.clone()).build();
+        assert_eq!(entity.req_typed_key::<Chapter>().unwrap().as_key(), &key);
+        assert!(entity.req_typed_key::<Book>().is_err());
+
+        let entity = EntityBuilder::new().with_key(Key::default()).build();
+        assert!(entity.req_typed_key::<Book>().is_err());
+        assert!(EntityBuilder::new()
+            .build()
+            .req_typed_key::<Book>()
+            .is_err());
+    }
+
+    #[test]
+    fn with_parent_keeps_the_parent_partition() {
+        let parent = Key::builder()
+            .project("library")
+            .namespace("fiction")
+            .name("Book", "book_one")
+            .build();
+        let key = TypedKey::<Chapter>::id(12).with_parent(parent.clone());
+
+        assert_eq!(key.as_key().partition_id, parent.partition_id);
+        assert_eq!(key.parent(), Some(parent.clone()));
+        assert_eq!(
+            Key::from(key),
+            Key::builder().parent(parent).id("Chapter", 12).build()
+        );
+
+        let key = TypedKey::<Chapter>::incomplete().with_parent(TypedKey::<Book>::name("b"));
+        assert_eq!(key.as_key().leaf_kind(), Some("Chapter"));
+        assert!(!key.as_key().is_complete());
+    }
+}
@@ -0,0 +1,55 @@
+use crate::google::datastore::v1::{value::ValueType, ArrayValue, Value};
+
+/// The `meaning` Datastore uses to mark an array of doubles as a vector embedding.
+pub(crate) const VECTOR_MEANING: i32 = 31;
+
+///
+/// A vector embedding, stored in Datastore as an array of doubles marked with the vector meaning.
+/// Vector values are always excluded from the built-in indexes; vector indexes are configured
+/// separately.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Vector(pub Vec<f64>);
+
+impl Vector {
+    pub fn as_slice(&self) -> &[f64] {
+        &self.0
+    }
+}
+
+impl From<Vec<f64>> for Vector {
+    fn from(values: Vec<f64>) -> Self {
+        Vector(values)
+    }
+}
+
+impl From<&[f64]> for Vector {
+    fn from(values: &[f64]) -> Self {
+        Vector(values.to_vec())
+    }
+}
+
+impl From<Vector> for Vec<f64> {
+    fn from(vector: Vector) -> Self {
+        vector.0
+    }
+}
+
+impl From<Vector> for Value {
+    fn from(vector: Vector) -> Self {
+        Value {
+            meaning: VECTOR_MEANING,
+            exclude_from_indexes: true,
+            value_type: Some(ValueType::ArrayValue(ArrayValue {
+                values: vector
+                    .0
+                    .into_iter()
+                    .map(|v| Value {
+                        value_type: Some(ValueType::DoubleValue(v)),
+                        ..Default::default()
+                    })
+                    .collect(),
+            })),
+        }
+    }
+}